@@ -0,0 +1,39 @@
+//! Links the host unit tests with `defmt`'s interned strings
+//!
+//! The `defmt` tests decode frames with the test program's `defmt` table. On
+//! embedded targets, `defmt.x` places the table. On a host, this script places
+//! it after the host's default sections. See `defmt`'s `defmt.x` for details.
+
+use std::{env, fs, path::PathBuf};
+
+const DEFMT_HOST: &str = "
+PROVIDE(_defmt_timestamp = __defmt_default_timestamp);
+PROVIDE(_defmt_panic = __defmt_default_panic);
+
+SECTIONS
+{
+  .defmt 1 (INFO) :
+  {
+    . = 1;
+    *(.defmt.prim.*);
+    *(.defmt.*);
+    KEEP(*(.defmt.end .defmt.end.*));
+  }
+} INSERT AFTER .comment;
+";
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if env::var_os("CARGO_FEATURE_DEFMT").is_none() || target_os != "linux" {
+        return;
+    }
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let script = out.join("defmt-host.x");
+    fs::write(&script, DEFMT_HOST).unwrap();
+    // Interned string indices are symbol addresses, so the program can't be
+    // position independent.
+    println!("cargo:rustc-link-arg=-T{}", script.display());
+    println!("cargo:rustc-link-arg=-no-pie");
+}
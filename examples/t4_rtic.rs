@@ -0,0 +1,80 @@
+//! DMA-based serial logging in an RTIC application - Teensy 4 example
+//!
+//! This uses the same setup as the `t4_dma.rs` example. Connect
+//! a serial receiver to pin 14, and you should receive log messages
+//! from the idle loop. The logger's UART and DMA channel are an RTIC
+//! resource of the task that's bound to the DMA channel's interrupt.
+
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use imxrt_hal::dma::{Buffer, Circular};
+use imxrt_hal::ral::Interrupt;
+use imxrt_uart_log::rtic::Logger;
+
+const BAUD: u32 = 115_200;
+
+#[repr(align(512))]
+struct Alignment(Buffer<[u8; 512]>);
+static BUFFER: Alignment = Alignment(Buffer::new([0; 512]));
+
+#[rtic::app(device = imxrt_hal::ral, peripherals = false)]
+const APP: () = {
+    struct Resources {
+        logger: Logger,
+    }
+
+    #[init]
+    fn init(_: init::Context) -> init::LateResources {
+        let teensy4_bsp::Peripherals {
+            uart,
+            mut ccm,
+            iomuxc,
+            dma,
+            ..
+        } = teensy4_bsp::Peripherals::take().unwrap();
+        let pins = teensy4_bsp::t40::into_pins(iomuxc);
+
+        //
+        // DMA initialization
+        //
+        let mut dma_channels = dma.clock(&mut ccm.handle);
+        let mut channel = dma_channels[7].take().unwrap();
+        channel.set_interrupt_on_completion(true);
+
+        //
+        // UART initialization
+        //
+        let uarts = uart.clock(
+            &mut ccm.handle,
+            imxrt_hal::ccm::uart::ClockSelect::OSC,
+            imxrt_hal::ccm::uart::PrescalarSelect::DIVIDE_1,
+        );
+        let uart = uarts.uart2.init(pins.p14, pins.p15, BAUD).unwrap();
+        let (tx, _) = uart.split();
+
+        let queue = cortex_m::singleton!(: [u8; 1024] = [0; 1024]).unwrap();
+        imxrt_uart_log::rtic::init(queue, Interrupt::DMA7_DMA23, Default::default()).unwrap();
+        let logger = Logger::new(tx, channel, Circular::new(&BUFFER.0).unwrap());
+
+        init::LateResources { logger }
+    }
+
+    #[idle]
+    fn idle(_: idle::Context) -> ! {
+        let mut counter: u32 = 0;
+        loop {
+            log::info!("Hello from idle! Counter is {}", counter);
+            counter = counter.wrapping_add(1);
+            // About one second at 600MHz
+            cortex_m::asm::delay(600_000_000);
+        }
+    }
+
+    #[task(binds = DMA7_DMA23, resources = [logger])]
+    fn dma7_dma23(cx: dma7_dma23::Context) {
+        cx.resources.logger.poll();
+    }
+};
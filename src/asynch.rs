@@ -0,0 +1,205 @@
+//! Asynchronous logging with `imxrt-hal` 0.5 DMA transfers
+//!
+//! The async logger splits logging into two halves:
+//!
+//! - the `log` macros copy each record into a buffer that you supply, and return.
+//! - [`run`](fn.run.html), an `async fn`, awaits the enqueued records, and sends them to an
+//!   `imxrt-hal` 0.5 `Lpuart` with DMA transfers. It awaits each transfer's completion.
+//!
+//! Spawn `run()` as a task in an async executor, like `embassy-executor`. The task never
+//! returns, and it only wakes when there are records to send. Enable the `"async"` feature
+//! to use this module.
+//!
+//! ```no_run
+//! # use imxrt_hal_05 as imxrt_hal;
+//! use imxrt_hal::{dma::channel::Channel, lpuart::Lpuart};
+//! # type Pins = ();
+//!
+//! // Spawn this in your executor
+//! async fn logger(mut uart: Lpuart<Pins, 2>, mut channel: Channel) -> ! {
+//!     imxrt_uart_log::asynch::run(&mut uart, &mut channel).await
+//! }
+//!
+//! let buffer = cortex_m::singleton!(: [u8; 2048] = [0; 2048]).unwrap();
+//! imxrt_uart_log::asynch::init(buffer, Default::default()).unwrap();
+//!
+//! log::info!("Sent by the logger task");
+//! ```
+//!
+//! The `imxrt-hal` DMA transfer wakes the task from the DMA channel's interrupt. Enable the
+//! channel's interrupt on completion, and call the HAL's `on_interrupt()` from the interrupt
+//! handler. See the `imxrt-hal` DMA documentation for more information.
+//!
+//! # Implementation
+//!
+//! The logger enqueues records in an interrupt free critical section. If you fill the buffer
+//! before the task sends the enqueued records, the logger **drops** the records that don't
+//! fit, and [`dropped()`](fn.dropped.html) counts them. The task transfers records straight
+//! from the buffer; it never copies them.
+//!
+//! The panic handler can't wait for the task, so it writes nothing through the async logger.
+
+use crate::{
+    dispatch::{self, Backend},
+    early, lpuart,
+    ring::Ring,
+    Filters, LoggingConfig, SetLoggerError,
+};
+use core::{
+    cell::RefCell,
+    fmt, future, slice,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Poll, Waker},
+};
+use critical_section::Mutex;
+use imxrt_hal_05::{dma::channel::Channel, lpuart::Lpuart};
+
+struct Queue {
+    ring: Ring<&'static mut [u8]>,
+    filters: Filters,
+}
+
+/// Number of records dropped by the async logger
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+impl Queue {
+    /// Enqueue a record, or drop it if it doesn't fit
+    fn write(&mut self, args: fmt::Arguments) {
+        if !self.ring.push_fmt(args) {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+struct State {
+    queue: Option<Queue>,
+    /// Wakes the task that's waiting for records
+    waker: Option<Waker>,
+}
+
+impl State {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The logger's state
+static STATE: Mutex<RefCell<State>> = Mutex::new(RefCell::new(State {
+    queue: None,
+    waker: None,
+}));
+
+pub(crate) fn enabled(metadata: &::log::Metadata) -> bool {
+    metadata.level() <= ::log::max_level()
+        && critical_section::with(|cs| {
+            let state = STATE.borrow(cs).borrow();
+            matches!(state.queue.as_ref(), Some(queue) if queue.filters.is_enabled(metadata))
+        })
+}
+
+pub(crate) fn log(record: &::log::Record) {
+    if record.level() > ::log::max_level() {
+        return;
+    }
+    critical_section::with(|cs| {
+        let mut state = STATE.borrow(cs).borrow_mut();
+        if let Some(queue) = state.queue.as_mut() {
+            if queue.filters.is_enabled(record.metadata()) {
+                queue.write(format_args!(
+                    "[{} {}]: {}\r\n",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ));
+                state.wake();
+            }
+        }
+    })
+}
+
+pub(crate) fn print(args: fmt::Arguments) {
+    critical_section::with(|cs| {
+        let mut state = STATE.borrow(cs).borrow_mut();
+        if let Some(queue) = state.queue.as_mut() {
+            queue.write(args);
+            state.wake();
+        }
+    })
+}
+
+/// Initialize the async logger with a buffer
+///
+/// The logger enqueues records in `buffer`. Spawn [`run`](fn.run.html) to send the records.
+///
+/// Returns an error if you've already called `init()`, or if you've already specified a logger
+/// through another interface.
+///
+/// If another logger in this crate is initialized, this logger replaces it. See
+/// [Switching loggers](../index.html#switching-loggers) for more information.
+pub fn init(buffer: &'static mut [u8], config: LoggingConfig) -> Result<(), SetLoggerError> {
+    critical_section::with(move |cs| {
+        let mut state = STATE.borrow(cs).borrow_mut();
+        if state.queue.is_some() {
+            return Err(SetLoggerError(()));
+        }
+
+        dispatch::activate(Backend::Async, config.max_level)?;
+        let mut ring = Ring::new(buffer);
+        early::replay(|bytes| {
+            ring.push(bytes);
+        });
+        state.queue = Some(Queue {
+            ring,
+            filters: Filters(config.filters),
+        });
+        state.wake();
+        Ok(())
+    })
+}
+
+/// Returns the number of records that the async logger dropped
+///
+/// The logger drops a record if it doesn't fit in the buffer's free space.
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Send the async logger's records to `uart`
+///
+/// `run()` waits for records, then sends them with DMA transfers on `channel`. It never
+/// returns. You may call `run()` before [`init()`](fn.init.html); it waits for the logger.
+/// Only run one `run()` task at a time.
+///
+/// If a transfer fails, the logger drops the transfer's records.
+pub async fn run<P, const N: u8>(uart: &mut Lpuart<P, N>, channel: &mut Channel) -> ! {
+    loop {
+        let (ptr, len) = future::poll_fn(|context| {
+            critical_section::with(|cs| {
+                let mut state = STATE.borrow(cs).borrow_mut();
+                match state.queue.as_ref().map(|queue| queue.ring.as_slices().0) {
+                    Some(bytes) if !bytes.is_empty() => {
+                        Poll::Ready((bytes.as_ptr(), bytes.len().min(lpuart::MAX_TRANSFER)))
+                    }
+                    _ => {
+                        state.waker = Some(context.waker().clone());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await;
+        // Safety: the bytes are in a static buffer. The logger only appends into
+        // the free space after the enqueued bytes, so it won't modify these bytes
+        // until we consume them.
+        let bytes = unsafe { slice::from_raw_parts(ptr, len) };
+        // Nothing to do if the transfer fails
+        let _ = uart.dma_write(channel, bytes).await;
+        critical_section::with(|cs| {
+            if let Some(queue) = STATE.borrow(cs).borrow_mut().queue.as_mut() {
+                queue.ring.consume(len);
+            }
+        });
+    }
+}
@@ -25,6 +25,42 @@
 //! Consider using the largest size transfer FIFO to support UART data transfer. See the example
 //! for more details.
 //!
+//! # Preemptible logging
+//!
+//! If you cannot tolerate disabled interrupts while the logger writes, initialize the logger
+//! with [`init_preemptible`](fn.init_preemptible.html). The logger writes with interrupts
+//! enabled, so higher-priority interrupts may run while a long message drains into the FIFO.
+//!
+//! The logger serializes writers with a lock that never blocks. If an interrupt preempts a
+//! writer, and the interrupt tries to log, the logger **drops the interrupt's message**. Waiting
+//! for the lock would deadlock, since the preempted writer cannot finish until the interrupt
+//! returns. Use [`dropped`](fn.dropped.html) to learn how many messages were dropped.
+//!
+//! # Timeouts
+//!
+//! By default, the logger waits forever for the transmit FIFO to accept data. If the FIFO
+//! never drains, perhaps because CTS flow control is asserted or the line is disconnected,
+//! the logger never returns. Use [`set_poll_timeout`](fn.set_poll_timeout.html) to bound the wait:
+//!
+//! ```no_run
+//! use core::num::NonZeroU32;
+//!
+//! imxrt_uart_log::blocking::set_poll_timeout(NonZeroU32::new(100_000));
+//! ```
+//!
+//! If the FIFO stays full for the specified number of polls, the logger abandons the rest of
+//! the message, and counts it as [`dropped`](fn.dropped.html). Then, it tries to write `"\r\n"`,
+//! with the same timeout, so that the next message starts on its own line.
+//!
+//! The timeout is a number of polls, not CPU cycles, so it does not depend on a running cycle
+//! counter. Measure the time for one poll on your system if you need an approximate duration.
+//!
+//! # Releasing the UART
+//!
+//! Use [`release`](fn.release.html) to take back the UART transfer half. You may reconfigure
+//! the UART, or hand it to other software, and then call `init()` again. The logger stays
+//! registered with the `log` crate, and it discards messages while it has no UART.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -60,83 +96,237 @@
 //! ```
 
 mod sink;
-use sink::Sink;
+#[cfg(any(feature = "panic-handler", feature = "fault"))]
+pub(crate) use crate::timeout::poll;
+pub use crate::timeout::set_poll_timeout;
+pub use sink::Sink;
+
+use crate::{
+    dispatch::{self, Backend},
+    early,
+    lock::TryLock,
+    Filters, LoggingConfig, SetLoggerError,
+};
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// Describes how the logger serializes writers
+#[derive(Clone, Copy)]
+enum Interrupts {
+    /// Write in an interrupt free critical section
+    Disabled,
+    /// Write with interrupts enabled, dropping any
+    /// messages from contexts that preempt a writer
+    Enabled,
+}
+
+/// Counts the messages that the logger could not write
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// The logger, if initialized
+///
+/// The lock serializes writers.
+static LOGGER: TryLock<Option<Logger>> = TryLock::new(None);
 
-use crate::{Filters, LoggingConfig, SetLoggerError};
-use core::cell::RefCell;
-use cortex_m::interrupt::{self, Mutex};
+/// `true` if the logger writes with interrupts enabled
+static PREEMPTIBLE: AtomicBool = AtomicBool::new(false);
 
 struct Logger {
     /// The peripheral
-    uart: Mutex<RefCell<Sink>>,
+    uart: Sink,
     /// A collection of targets that we are expected
     /// to filter. If this is empty, we allow everything
     filters: Filters,
 }
 
-impl log::Log for Logger {
+impl Logger {
     fn enabled(&self, metadata: &::log::Metadata) -> bool {
         metadata.level() <= ::log::max_level() // The log level is appropriate
             && self.filters.is_enabled(metadata) // The target is in the filter list
     }
 
-    fn log(&self, record: &::log::Record) {
-        if self.enabled(record.metadata()) {
-            interrupt::free(|cs| {
-                let uart = self.uart.borrow(cs);
-                let mut uart = uart.borrow_mut();
-                use core::fmt::Write;
-                write!(
-                    uart,
-                    "[{} {}]: {}\r\n",
-                    record.level(),
-                    record.target(),
-                    record.args()
-                )
-                .expect("write never fails");
-            });
+    fn write(&mut self, record: &::log::Record) {
+        let result = write!(
+            self.uart,
+            "[{} {}]: {}\r\n",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if result.is_err() {
+            // Timed out waiting for the FIFO
+            DROPPED.fetch_add(1, Ordering::Relaxed);
         }
     }
+}
 
-    fn flush(&self) {
-        interrupt::free(|cs| {
-            let uart = self.uart.borrow(cs);
-            let mut uart = uart.borrow_mut();
-            uart.flush();
-        })
+/// Run `f` with the logger, respecting the logger's interrupt setting
+///
+/// Returns `None` if another context holds the logger.
+fn with_logger<R>(f: impl FnOnce(&mut Option<Logger>) -> R) -> Option<R> {
+    let run = || LOGGER.try_lock().map(|mut logger| f(&mut logger));
+    if PREEMPTIBLE.load(Ordering::Relaxed) {
+        run()
+    } else {
+        critical_section::with(|_| run())
     }
 }
 
+pub(crate) fn enabled(metadata: &::log::Metadata) -> bool {
+    with_logger(|logger| matches!(logger.as_ref(), Some(logger) if logger.enabled(metadata)))
+        .unwrap_or(false)
+}
+
+pub(crate) fn log(record: &::log::Record) {
+    let written = with_logger(|logger| {
+        if let Some(logger) = logger {
+            if logger.enabled(record.metadata()) {
+                logger.write(record);
+            }
+        }
+    });
+    if written.is_none() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn print(args: core::fmt::Arguments) {
+    let written = with_logger(|logger| {
+        if let Some(logger) = logger {
+            if logger.uart.write_fmt(args).is_err() {
+                // Timed out waiting for the FIFO
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+    if written.is_none() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn flush() {
+    // If another context holds the logger, it will finish
+    // writing when we return.
+    with_logger(|logger| {
+        if let Some(logger) = logger {
+            // Nothing to do if we time out
+            let _ = logger.uart.flush();
+        }
+    });
+}
+
 /// Initialize the blocking logger with a UART's transfer half
 ///
 /// `tx` should be an `imxrt_hal::uart::Tx` half, obtained by calling `split()`
-/// on a configured `UART` peripheral. Returns an error if you've already called `init()`, or if
+/// on a configured `UART` peripheral. Returns an error if you've already called `init()`, and
+/// you haven't [`release()`](fn.release.html)d the logger. Also returns an error if
 /// you've already specified a logger through another interface.
 ///
+/// If the other logger is initialized, this logger replaces it. See
+/// [Switching loggers](../index.html#switching-loggers) for more information.
+///
 /// See the [module-level documentation](index.html#example) for an example.
 pub fn init<S>(tx: S, config: LoggingConfig) -> Result<(), SetLoggerError>
 where
     S: Into<Sink>,
 {
-    static LOGGER: Mutex<RefCell<Option<Logger>>> = Mutex::new(RefCell::new(None));
-    interrupt::free(|cs| {
-        let logger = LOGGER.borrow(cs);
-        let mut logger = logger.borrow_mut();
-        if logger.is_none() {
-            *logger = Some(Logger {
-                uart: Mutex::new(RefCell::new(tx.into())),
-                filters: Filters(config.filters),
-            });
+    init_logger(tx.into(), config, Interrupts::Disabled)
+}
+
+/// Initialize a blocking logger that does not disable interrupts
+///
+/// `init_preemptible()` behaves like [`init()`](fn.init.html), but the logger writes
+/// with interrupts enabled. See [Preemptible logging](index.html#preemptible-logging)
+/// for the trade-offs.
+pub fn init_preemptible<S>(tx: S, config: LoggingConfig) -> Result<(), SetLoggerError>
+where
+    S: Into<Sink>,
+{
+    init_logger(tx.into(), config, Interrupts::Enabled)
+}
+
+/// Returns the number of messages that the logger dropped
+///
+/// See [Preemptible logging](index.html#preemptible-logging) and [Timeouts](index.html#timeouts)
+/// to understand why the logger might drop messages. The count wraps on overflow.
+///
+/// A preemptible logger cannot check the filters of messages that it drops. The count may
+/// include messages that the logger would have filtered.
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+fn init_logger(
+    sink: Sink,
+    config: LoggingConfig,
+    interrupts: Interrupts,
+) -> Result<(), SetLoggerError> {
+    critical_section::with(|_| {
+        let mut logger = LOGGER.try_lock().ok_or(SetLoggerError(()))?;
+        if logger.is_some() {
+            return Err(SetLoggerError(()));
         }
 
-        // Safety: transmute from limited lifetime 'a to 'static lifetime
-        // is OK, since the derived memory has 'static lifetime. The need
-        // for this comes from the `interrupt::free()` and `Mutex::borrow()`
-        // interplay. The two require any references to be tied to the
-        // lifetime of the critical section.
-        let logger: &'static Logger = unsafe { core::mem::transmute(logger.as_ref().unwrap()) };
-        ::log::set_logger(logger)
-            .map(|_| ::log::set_max_level(config.max_level))
-            .map_err(From::from)
+        dispatch::activate(Backend::Blocking, config.max_level)?;
+        PREEMPTIBLE.store(matches!(interrupts, Interrupts::Enabled), Ordering::Relaxed);
+        let mut uart = sink;
+        early::replay(|bytes| {
+            // Nothing to do if we time out
+            let _ = uart.write_bytes(bytes);
+        });
+        *logger = Some(Logger {
+            uart,
+            filters: Filters(config.filters),
+        });
+        Ok(())
+    })
+}
+
+/// Release the blocking logger's UART
+///
+/// `release()` waits for the transmit FIFO to drain, then returns the UART transfer half that
+/// you supplied to `init()`. Use `TryFrom` to convert the [`Sink`](enum.Sink.html) into your
+/// `imxrt_hal::uart::Tx` half. After `release()`, the logger discards all messages until you
+/// call `init()` again.
+///
+/// Returns `None` if there is no logger. Also returns `None` if `release()` preempted a
+/// [preemptible](index.html#preemptible-logging) logger while it was writing.
+///
+/// ```no_run
+/// use core::convert::TryFrom;
+/// use imxrt_hal::{iomuxc::consts::U2, uart::Tx};
+///
+/// let sink = imxrt_uart_log::blocking::release().unwrap();
+/// let tx = Tx::<U2>::try_from(sink).ok().unwrap();
+/// ```
+pub fn release() -> Option<Sink> {
+    with_logger(|logger| {
+        let mut logger = logger.take()?;
+        // Nothing to do if we time out
+        let _ = logger.uart.flush();
+        Some(logger.uart)
+    })
+    .flatten()
+}
+
+/// Write `args` through the blocking logger, even if another context is writing
+///
+/// Returns `false` if there is no blocking logger. Only call this if you will never
+/// return to a preempted context, like in a panic handler.
+#[cfg(any(feature = "panic-handler", feature = "fault"))]
+pub(crate) fn emergency_write(args: core::fmt::Arguments) -> bool {
+    critical_section::with(|_| {
+        // Safety: caller never returns to the context that holds the lock
+        let mut logger = unsafe { LOGGER.steal() };
+        if let Some(logger) = logger.as_mut() {
+            // Nothing to do if we time out
+            let _ = logger.uart.write_fmt(args);
+            let _ = logger.uart.flush();
+            true
+        } else {
+            false
+        }
     })
 }
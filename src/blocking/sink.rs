@@ -1,10 +1,11 @@
 //! Logging sink
 
-use core::fmt;
+use crate::timeout::poll;
+use core::{convert::TryFrom, fmt};
 use imxrt_hal::iomuxc;
 use imxrt_hal::uart;
 
-use embedded_hal::blocking::serial::Write;
+use embedded_hal::serial::Write;
 
 // The implementation has a few `expects()` that assume no errors
 // from the UART write operations. This statically asserts that the
@@ -18,7 +19,32 @@ impl _IsInfallible for core::convert::Infallible {
 }
 const _UART_ERROR_INFALLIBLE: [u8; 1] = [0; <_Error as _IsInfallible>::VALUE as usize];
 
+fn write_all<W: Write<u8>>(uart: &mut W, bytes: &[u8]) -> fmt::Result {
+    for &byte in bytes {
+        if !poll(|| uart.write(byte).is_ok()) {
+            // Try to end the partial line, so the next message starts on its own line
+            let _ = b"\r\n"
+                .iter()
+                .all(|&byte| poll(|| uart.write(byte).is_ok()));
+            return Err(fmt::Error);
+        }
+    }
+    Ok(())
+}
+
+fn flush<W: Write<u8>>(uart: &mut W) -> fmt::Result {
+    if poll(|| uart.flush().is_ok()) {
+        Ok(())
+    } else {
+        Err(fmt::Error)
+    }
+}
+
 /// A logging sink which dispatches to any of the eight possible UART peripherals
+///
+/// Each variant holds the UART transfer half of the same number. Use `TryFrom` to
+/// convert a `Sink` back into the UART transfer half. If the `Sink` holds a different
+/// UART, the conversion returns the `Sink`.
 pub enum Sink {
     _1(uart::Tx<iomuxc::consts::U1>),
     _2(uart::Tx<iomuxc::consts::U2>),
@@ -32,32 +58,38 @@ pub enum Sink {
 
 impl fmt::Write for Sink {
     fn write_str(&mut self, string: &str) -> fmt::Result {
-        match self {
-            Sink::_1(uart) => uart.bwrite_all(string.as_bytes()).map_err(|_| fmt::Error),
-            Sink::_2(uart) => uart.bwrite_all(string.as_bytes()).map_err(|_| fmt::Error),
-            Sink::_3(uart) => uart.bwrite_all(string.as_bytes()).map_err(|_| fmt::Error),
-            Sink::_4(uart) => uart.bwrite_all(string.as_bytes()).map_err(|_| fmt::Error),
-            Sink::_5(uart) => uart.bwrite_all(string.as_bytes()).map_err(|_| fmt::Error),
-            Sink::_6(uart) => uart.bwrite_all(string.as_bytes()).map_err(|_| fmt::Error),
-            Sink::_7(uart) => uart.bwrite_all(string.as_bytes()).map_err(|_| fmt::Error),
-            Sink::_8(uart) => uart.bwrite_all(string.as_bytes()).map_err(|_| fmt::Error),
-        }
+        self.write_bytes(string.as_bytes())
     }
 }
 
 impl Sink {
-    pub(super) fn flush(&mut self) {
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) -> fmt::Result {
         match self {
-            Sink::_1(uart) => uart.bflush(),
-            Sink::_2(uart) => uart.bflush(),
-            Sink::_3(uart) => uart.bflush(),
-            Sink::_4(uart) => uart.bflush(),
-            Sink::_5(uart) => uart.bflush(),
-            Sink::_6(uart) => uart.bflush(),
-            Sink::_7(uart) => uart.bflush(),
-            Sink::_8(uart) => uart.bflush(),
+            Sink::_1(uart) => write_all(uart, bytes),
+            Sink::_2(uart) => write_all(uart, bytes),
+            Sink::_3(uart) => write_all(uart, bytes),
+            Sink::_4(uart) => write_all(uart, bytes),
+            Sink::_5(uart) => write_all(uart, bytes),
+            Sink::_6(uart) => write_all(uart, bytes),
+            Sink::_7(uart) => write_all(uart, bytes),
+            Sink::_8(uart) => write_all(uart, bytes),
+        }
+    }
+
+    /// Wait for the transmit FIFO to drain
+    ///
+    /// Returns an error if the FIFO does not drain before the timeout.
+    pub(crate) fn flush(&mut self) -> fmt::Result {
+        match self {
+            Sink::_1(uart) => flush(uart),
+            Sink::_2(uart) => flush(uart),
+            Sink::_3(uart) => flush(uart),
+            Sink::_4(uart) => flush(uart),
+            Sink::_5(uart) => flush(uart),
+            Sink::_6(uart) => flush(uart),
+            Sink::_7(uart) => flush(uart),
+            Sink::_8(uart) => flush(uart),
         }
-        .expect("flush never fails");
     }
 }
 
@@ -108,3 +140,83 @@ impl From<uart::Tx<iomuxc::consts::U8>> for Sink {
         Sink::_8(tx)
     }
 }
+
+impl TryFrom<Sink> for uart::Tx<iomuxc::consts::U1> {
+    type Error = Sink;
+    fn try_from(sink: Sink) -> Result<Self, Sink> {
+        match sink {
+            Sink::_1(tx) => Ok(tx),
+            sink => Err(sink),
+        }
+    }
+}
+
+impl TryFrom<Sink> for uart::Tx<iomuxc::consts::U2> {
+    type Error = Sink;
+    fn try_from(sink: Sink) -> Result<Self, Sink> {
+        match sink {
+            Sink::_2(tx) => Ok(tx),
+            sink => Err(sink),
+        }
+    }
+}
+
+impl TryFrom<Sink> for uart::Tx<iomuxc::consts::U3> {
+    type Error = Sink;
+    fn try_from(sink: Sink) -> Result<Self, Sink> {
+        match sink {
+            Sink::_3(tx) => Ok(tx),
+            sink => Err(sink),
+        }
+    }
+}
+
+impl TryFrom<Sink> for uart::Tx<iomuxc::consts::U4> {
+    type Error = Sink;
+    fn try_from(sink: Sink) -> Result<Self, Sink> {
+        match sink {
+            Sink::_4(tx) => Ok(tx),
+            sink => Err(sink),
+        }
+    }
+}
+
+impl TryFrom<Sink> for uart::Tx<iomuxc::consts::U5> {
+    type Error = Sink;
+    fn try_from(sink: Sink) -> Result<Self, Sink> {
+        match sink {
+            Sink::_5(tx) => Ok(tx),
+            sink => Err(sink),
+        }
+    }
+}
+
+impl TryFrom<Sink> for uart::Tx<iomuxc::consts::U6> {
+    type Error = Sink;
+    fn try_from(sink: Sink) -> Result<Self, Sink> {
+        match sink {
+            Sink::_6(tx) => Ok(tx),
+            sink => Err(sink),
+        }
+    }
+}
+
+impl TryFrom<Sink> for uart::Tx<iomuxc::consts::U7> {
+    type Error = Sink;
+    fn try_from(sink: Sink) -> Result<Self, Sink> {
+        match sink {
+            Sink::_7(tx) => Ok(tx),
+            sink => Err(sink),
+        }
+    }
+}
+
+impl TryFrom<Sink> for uart::Tx<iomuxc::consts::U8> {
+    type Error = Sink;
+    fn try_from(sink: Sink) -> Result<Self, Sink> {
+        match sink {
+            Sink::_8(tx) => Ok(tx),
+            sink => Err(sink),
+        }
+    }
+}
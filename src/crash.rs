@@ -0,0 +1,380 @@
+//! Crash logs that survive a warm reset
+//!
+//! The crash logger is a [memory](../memory/index.html) logger that keeps its state in the
+//! buffer, next to the records. If you place the buffer in RAM that's not initialized at
+//! startup, the records survive a watchdog or software reset. After the reset, use
+//! [`recover`](fn.recover.html) to find, and validate, the previous boot's records. Then, send
+//! them over a UART, and call [`init`](fn.init.html) to start a new crash log.
+//!
+//! ```no_run
+//! use core::mem::MaybeUninit;
+//! use embedded_hal::serial::Write;
+//! use imxrt_uart_log::crash;
+//! # fn uart2_tx() -> imxrt_hal::uart::Tx<imxrt_hal::iomuxc::consts::U2> { unimplemented!() }
+//!
+//! // cortex-m-rt does not initialize the .uninit section
+//! #[link_section = ".uninit.crash_log"]
+//! static mut CRASH_LOG: MaybeUninit<[u8; 4096]> = MaybeUninit::uninit();
+//!
+//! // Safety: we only take this reference once
+//! let buffer: &'static mut [u8] = unsafe {
+//!     let buffer = &mut *core::ptr::addr_of_mut!(CRASH_LOG);
+//!     // Every bit pattern is a valid byte array
+//!     &mut *buffer.as_mut_ptr()
+//! };
+//!
+//! let mut tx = uart2_tx();
+//! if let Some(previous) = crash::recover(buffer) {
+//!     for byte in previous.bytes() {
+//!         while tx.write(byte).is_err() {}
+//!     }
+//! }
+//!
+//! crash::init(buffer, Default::default()).unwrap();
+//! log::info!("Survives a warm reset");
+//! ```
+//!
+//! Use [`memory::read()`](../memory/fn.read.html), and the other `memory` functions, to read
+//! the current boot's records.
+//!
+//! # Buffer layout
+//!
+//! The first [`HEADER_SIZE`](constant.HEADER_SIZE.html) bytes of the buffer are a header. The
+//! header has a magic number, the position of the oldest record, the number of bytes in the log,
+//! and a CRC of those fields. All fields are little endian `u32`s. The rest of the buffer holds
+//! the records. The CRC protects the header, not the records; `recover()` rejects a log if the
+//! header is not valid, like after a power-on reset.
+//!
+//! The logger writes each record after the records that the header describes, then saves the
+//! header. If the record overwrites old records, the logger first saves a header that excludes
+//! the overwritten bytes. If a reset interrupts a write, `recover()` finds the records from
+//! before the write.
+
+use crate::{memory, ring::Records, LoggingConfig, SetLoggerError};
+use core::fmt;
+
+/// The number of bytes at the start of the buffer that hold the crash log's state
+pub const HEADER_SIZE: usize = 20;
+
+/// "ULOG" as a little endian `u32`
+const MAGIC: u32 = 0x474F_4C55;
+
+/// The header field offsets
+const MAGIC_OFFSET: usize = 0;
+const START_OFFSET: usize = 4;
+const LEN_OFFSET: usize = 8;
+const FLAGS_OFFSET: usize = 12;
+const CRC_OFFSET: usize = 16;
+
+/// Set in the flags if the oldest record may be incomplete
+const TRUNCATED: u32 = 1;
+
+/// Computes a CRC-32 (IEEE 802.3)
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buffer[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn write_u32(buffer: &mut [u8], offset: usize, value: u32) {
+    buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// A buffer for the memory logger
+///
+/// A persistent buffer starts with a header, and the records follow the header.
+pub(crate) struct Storage<B> {
+    buffer: B,
+    persistent: bool,
+}
+
+impl<B: AsRef<[u8]>> Storage<B> {
+    pub(crate) fn volatile(buffer: B) -> Self {
+        Storage {
+            buffer,
+            persistent: false,
+        }
+    }
+
+    fn persistent(buffer: B) -> Self {
+        Storage {
+            buffer,
+            persistent: true,
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Storage<B> {
+    /// Append `bytes` to the records
+    ///
+    /// If `bytes` overwrites old records, save the header before the overwrite. Call
+    /// [`save()`](#method.save) after the whole record.
+    pub(crate) fn write_bytes(records: &mut Records<Self>, bytes: &[u8]) {
+        records.write_bytes_with(bytes, Storage::save);
+    }
+
+    /// Append formatted `args` to the records, then save the header
+    pub(crate) fn write_fmt(records: &mut Records<Self>, args: fmt::Arguments) {
+        struct Writer<'a, B>(&'a mut Records<Storage<B>>);
+
+        impl<B: AsRef<[u8]> + AsMut<[u8]>> fmt::Write for Writer<'_, B> {
+            fn write_str(&mut self, string: &str) -> fmt::Result {
+                Storage::write_bytes(self.0, string.as_bytes());
+                Ok(())
+            }
+        }
+
+        // Never fails
+        let _ = fmt::write(&mut Writer(records), args);
+        Storage::save(records);
+    }
+
+    /// Save the records' state in the header, if the buffer is persistent
+    pub(crate) fn save(records: &mut Records<Self>) {
+        let (start, len, truncated) = records.parts();
+        let storage = records.get_mut();
+        if !storage.persistent {
+            return;
+        }
+        let header = &mut storage.buffer.as_mut()[..HEADER_SIZE];
+        write_u32(header, MAGIC_OFFSET, MAGIC);
+        write_u32(header, START_OFFSET, start as u32);
+        write_u32(header, LEN_OFFSET, len as u32);
+        write_u32(header, FLAGS_OFFSET, if truncated { TRUNCATED } else { 0 });
+        let crc = crc32(&header[..CRC_OFFSET]);
+        write_u32(header, CRC_OFFSET, crc);
+    }
+}
+
+impl<B: AsRef<[u8]>> AsRef<[u8]> for Storage<B> {
+    fn as_ref(&self) -> &[u8] {
+        let buffer = self.buffer.as_ref();
+        if self.persistent {
+            &buffer[HEADER_SIZE..]
+        } else {
+            buffer
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> AsMut<[u8]> for Storage<B> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        let buffer = self.buffer.as_mut();
+        if self.persistent {
+            &mut buffer[HEADER_SIZE..]
+        } else {
+            buffer
+        }
+    }
+}
+
+/// Validate the header, and return the records
+fn records(buffer: &[u8]) -> Option<Records<Storage<&[u8]>>> {
+    let header = buffer.get(..HEADER_SIZE)?;
+    if read_u32(header, MAGIC_OFFSET) != MAGIC
+        || read_u32(header, CRC_OFFSET) != crc32(&header[..CRC_OFFSET])
+    {
+        return None;
+    }
+    let flags = read_u32(header, FLAGS_OFFSET);
+    if flags & !TRUNCATED != 0 {
+        return None;
+    }
+    Records::from_parts(
+        Storage::persistent(buffer),
+        read_u32(header, START_OFFSET) as usize,
+        read_u32(header, LEN_OFFSET) as usize,
+        flags & TRUNCATED != 0,
+    )
+}
+
+/// The previous boot's records
+///
+/// See [`recover`](fn.recover.html) for more information.
+pub struct Recovered<'a> {
+    first: &'a [u8],
+    second: &'a [u8],
+}
+
+impl<'a> Recovered<'a> {
+    /// Returns the records, oldest first
+    ///
+    /// The records may wrap around the end of the buffer. The second slice holds the
+    /// records that wrapped.
+    pub fn as_slices(&self) -> (&'a [u8], &'a [u8]) {
+        (self.first, self.second)
+    }
+
+    /// Returns an iterator over the records' bytes, oldest first
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + 'a {
+        self.first.iter().chain(self.second.iter()).copied()
+    }
+
+    /// Returns the number of bytes in the records
+    pub fn len(&self) -> usize {
+        self.first.len() + self.second.len()
+    }
+
+    /// Returns `true` if there are no records
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Find the previous boot's records in `buffer`
+///
+/// Returns `None` if the buffer does not hold a valid crash log. This happens after a power-on
+/// reset, since the RAM holds random data. `recover()` does not modify the buffer; call it
+/// before [`init`](fn.init.html).
+pub fn recover(buffer: &[u8]) -> Option<Recovered<'_>> {
+    // Find the slices in `buffer`, since slices from the records
+    // would borrow the records
+    let (start, len) = records(buffer)?.complete().parts();
+    let data = &buffer[HEADER_SIZE..];
+    let end = start + len;
+    let (first, second) = if end <= data.len() {
+        (&data[start..end], &data[..0])
+    } else {
+        (&data[start..], &data[..end - data.len()])
+    };
+    Some(Recovered { first, second })
+}
+
+/// Initialize the memory logger with a crash log buffer
+///
+/// `init()` starts a new crash log, which discards the previous boot's records. Call
+/// [`recover`](fn.recover.html) first. `buffer` should be in RAM that's not initialized at
+/// startup, and it must be larger than [`HEADER_SIZE`](constant.HEADER_SIZE.html).
+///
+/// Returns an error if the buffer is too small, or for any reason that
+/// [`memory::init()`](../memory/fn.init.html) returns an error.
+pub fn init(buffer: &'static mut [u8], config: LoggingConfig) -> Result<(), SetLoggerError> {
+    if buffer.len() <= HEADER_SIZE {
+        return Err(SetLoggerError(()));
+    }
+    memory::init_storage(Storage::persistent(buffer), config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, recover, Storage, HEADER_SIZE};
+    use crate::ring::Records;
+
+    const SIZE: usize = HEADER_SIZE + 32;
+
+    fn write(records: &mut Records<Storage<[u8; SIZE]>>, bytes: &[u8]) {
+        Storage::write_bytes(records, bytes);
+        Storage::save(records);
+    }
+
+    #[test]
+    fn check_crc() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn uninitialized_memory() {
+        assert!(recover(&[0; SIZE]).is_none());
+        assert!(recover(&[0xA5; SIZE]).is_none());
+        assert!(recover(&[]).is_none());
+    }
+
+    #[test]
+    fn recover_records() {
+        let mut records = Records::new(Storage::persistent([0xA5; SIZE]));
+        write(&mut records, b"[INFO a]: 1\r\n");
+        write(&mut records, b"[INFO a]: 2\r\n");
+        let buffer = records.into_inner().into_inner();
+
+        let recovered = recover(&buffer).unwrap();
+        assert_eq!(recovered.len(), 26);
+        assert!(recovered
+            .bytes()
+            .eq(b"[INFO a]: 1\r\n[INFO a]: 2\r\n".iter().copied()));
+    }
+
+    #[test]
+    fn recover_wrapped_records() {
+        let mut records = Records::new(Storage::persistent([0; SIZE]));
+        write(&mut records, b"[INFO a]: 1\r\n");
+        write(&mut records, b"[INFO a]: 2\r\n");
+        write(&mut records, b"[INFO a]: 3\r\n");
+        let buffer = records.into_inner().into_inner();
+
+        // Part of the first record was overwritten, and
+        // the rest of the first record is discarded
+        let recovered = recover(&buffer).unwrap();
+        let (first, second) = recovered.as_slices();
+        assert!(!second.is_empty());
+        assert_eq!(first.len() + second.len(), 26);
+        assert!(recovered
+            .bytes()
+            .eq(b"[INFO a]: 2\r\n[INFO a]: 3\r\n".iter().copied()));
+    }
+
+    #[test]
+    fn corrupt_header() {
+        let mut records = Records::new(Storage::persistent([0; SIZE]));
+        write(&mut records, b"[INFO a]: 1\r\n");
+        let buffer = records.into_inner().into_inner();
+
+        for idx in 0..HEADER_SIZE {
+            let mut corrupt = buffer;
+            corrupt[idx] ^= 0x10;
+            assert!(recover(&corrupt).is_none(), "{}", idx);
+        }
+    }
+
+    #[test]
+    fn interrupted_write() {
+        let mut records = Records::new(Storage::persistent([0; SIZE]));
+        write(&mut records, b"[INFO a]: 1\r\n");
+
+        // A reset after the logger writes the record, and before it saves the header
+        Storage::write_bytes(&mut records, b"[INFO a]: 2\r\n");
+        let recovered = recover(&records.get_mut().buffer).unwrap();
+        assert!(recovered.bytes().eq(b"[INFO a]: 1\r\n".iter().copied()));
+
+        Storage::save(&mut records);
+        assert_eq!(recover(&records.get_mut().buffer).unwrap().len(), 26);
+    }
+
+    #[test]
+    fn interrupted_overwrite() {
+        let mut records = Records::new(Storage::persistent([0; SIZE]));
+        write(&mut records, b"[INFO a]: 1\r\n");
+        write(&mut records, b"[INFO a]: 2\r\n");
+
+        // The third record overwrites the first record. A reset before
+        // the logger saves the header keeps the second record.
+        Storage::write_bytes(&mut records, b"[INFO a]: 3\r\n");
+        let recovered = recover(&records.get_mut().buffer).unwrap();
+        assert!(recovered.bytes().eq(b"[INFO a]: 2\r\n".iter().copied()));
+    }
+
+    #[test]
+    fn invalid_indices() {
+        // A valid CRC can't save a header with out-of-bounds indices
+        let mut records = Records::new(Storage::persistent([0; HEADER_SIZE + 64]));
+        records.write_bytes(&[b'x'; 64]);
+        Storage::save(&mut records);
+        let buffer = records.into_inner().into_inner();
+        assert!(recover(&buffer[..SIZE]).is_none());
+    }
+}
@@ -0,0 +1,259 @@
+//! Suppress repeated log records
+//!
+//! A loop that logs the same error on every iteration can bury the records around it.
+//! When you enable duplicate suppression, the logger writes the first of a run of identical
+//! records, and drops the rest. When a different record arrives, the logger writes a summary
+//! before it, like `syslog`:
+//!
+//! ```text
+//! [WARN sensor]: no response from 0x42
+//! [WARN sensor]: last message repeated 57 times
+//! [INFO app]: retrying
+//! ```
+//!
+//! Records are identical if they have the same level, target, and message. Duplicate
+//! suppression works with every logger in this crate. Enable it with
+//! [`set_enabled()`](fn.set_enabled.html):
+//!
+//! ```no_run
+//! imxrt_uart_log::dedup::set_enabled(true);
+//! ```
+//!
+//! To write a pending summary without waiting for a different record, flush the logger:
+//!
+//! ```no_run
+//! log::logger().flush();
+//! ```
+//!
+//! # Implementation
+//!
+//! The logger doesn't keep the last message. Instead, it formats each record into a 32-bit
+//! hash, then formats the record again when it writes the record. The second formatting
+//! increases the time that it takes to log. Two different records may have the same hash, and
+//! the logger drops the second record. The summary names the first 32 bytes of the target.
+//!
+//! If you also use the [rate limiter](../ratelimit/index.html), the logger checks the rate
+//! limiter first. Duplicate suppression only sees the records that the rate limiter admits.
+
+use crate::{dispatch, fnv::Fnv, name::Name};
+use core::{
+    cell::RefCell,
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use critical_section::Mutex;
+
+/// A run of identical records
+#[derive(Clone, Copy)]
+struct Run {
+    hash: u32,
+    level: ::log::Level,
+    target: Name,
+    /// The number of records after the first
+    repeated: u32,
+}
+
+impl fmt::Display for Run {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{} {}]: last message repeated {} times\r\n",
+            self.level, self.target, self.repeated
+        )
+    }
+}
+
+/// What to do with a record
+enum Admit {
+    /// The record repeats the last record
+    Drop,
+    /// Write the record
+    Log,
+    /// Write the summary of the last record's run, then the record
+    Summarize(Run),
+}
+
+/// The last record, if any
+struct Last(Option<Run>);
+
+/// Hash the record's level, target, and message
+fn hash(record: &::log::Record) -> u32 {
+    let mut fnv = Fnv::new();
+    // Never fails
+    let _ = write!(
+        fnv,
+        "{}\0{}\0{}",
+        record.level(),
+        record.target(),
+        record.args()
+    );
+    fnv.finish()
+}
+
+impl Last {
+    /// Compare the record's `hash` to the last record's hash
+    fn admit(&mut self, hash: u32, level: ::log::Level, target: &str) -> Admit {
+        match &mut self.0 {
+            Some(run) if run.hash == hash => {
+                run.repeated = run.repeated.saturating_add(1);
+                Admit::Drop
+            }
+            last => {
+                let summary = last.take().filter(|run| run.repeated > 0);
+                *last = Some(Run {
+                    hash,
+                    level,
+                    target: Name::truncate(target),
+                    repeated: 0,
+                });
+                summary.map_or(Admit::Log, Admit::Summarize)
+            }
+        }
+    }
+
+    /// Returns the summary of the last record's run, if it repeated
+    ///
+    /// Later identical records start a new count.
+    fn take_summary(&mut self) -> Option<Run> {
+        let run = self.0.as_mut().filter(|run| run.repeated > 0)?;
+        let summary = *run;
+        run.repeated = 0;
+        Some(summary)
+    }
+}
+
+/// `true` if the logger suppresses repeated records
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static LAST: Mutex<RefCell<Last>> = Mutex::new(RefCell::new(Last(None)));
+
+/// Returns `true` if the logger suppresses repeated records
+pub(crate) fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns `true` if the dispatcher should log `record`
+///
+/// Only call this for records that the active logger would write.
+pub(crate) fn admit(record: &::log::Record) -> bool {
+    if !is_enabled() {
+        return true;
+    }
+    // Format the message outside of the critical section
+    let hash = hash(record);
+    let admit = critical_section::with(|cs| {
+        LAST.borrow(cs)
+            .borrow_mut()
+            .admit(hash, record.level(), record.target())
+    });
+    match admit {
+        Admit::Drop => false,
+        Admit::Log => true,
+        Admit::Summarize(run) => {
+            dispatch::print(format_args!("{}", run));
+            true
+        }
+    }
+}
+
+/// Write the summary of the last record's run, if it repeated
+pub(crate) fn flush() {
+    if let Some(run) = critical_section::with(|cs| LAST.borrow(cs).borrow_mut().take_summary()) {
+        dispatch::print(format_args!("{}", run));
+    }
+}
+
+/// Enable or disable duplicate suppression
+///
+/// Duplicate suppression is disabled by default. When you disable it, the logger writes the
+/// pending summary, if any.
+pub fn set_enabled(enabled: bool) {
+    let was_enabled = ENABLED.swap(enabled, Ordering::Relaxed);
+    if was_enabled && !enabled {
+        flush();
+        critical_section::with(|cs| LAST.borrow(cs).borrow_mut().0 = None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash, Admit, Last};
+    use log::Level;
+
+    fn admit(last: &mut Last, level: Level, target: &str, message: &str) -> Admit {
+        let hash = hash(
+            &::log::Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{}", message))
+                .build(),
+        );
+        last.admit(hash, level, target)
+    }
+
+    #[test]
+    fn collapse_repeats() {
+        let mut last = Last(None);
+        assert!(matches!(
+            admit(&mut last, Level::Warn, "sensor", "no response"),
+            Admit::Log
+        ));
+        assert!(matches!(
+            admit(&mut last, Level::Warn, "sensor", "no response"),
+            Admit::Drop
+        ));
+        assert!(matches!(
+            admit(&mut last, Level::Warn, "sensor", "no response"),
+            Admit::Drop
+        ));
+        match admit(&mut last, Level::Info, "app", "retrying") {
+            Admit::Summarize(run) => {
+                assert_eq!(run.level, Level::Warn);
+                assert_eq!(run.target.as_str(), "sensor");
+                assert_eq!(run.repeated, 2);
+            }
+            _ => panic!("expected a summary"),
+        }
+        assert!(matches!(
+            admit(&mut last, Level::Warn, "sensor", "no response"),
+            Admit::Log
+        ));
+    }
+
+    #[test]
+    fn compare_level_target_and_message() {
+        let mut last = Last(None);
+        assert!(matches!(
+            admit(&mut last, Level::Warn, "sensor", "a"),
+            Admit::Log
+        ));
+        assert!(matches!(
+            admit(&mut last, Level::Error, "sensor", "a"),
+            Admit::Log
+        ));
+        assert!(matches!(
+            admit(&mut last, Level::Error, "motor", "a"),
+            Admit::Log
+        ));
+        assert!(matches!(
+            admit(&mut last, Level::Error, "motor", "b"),
+            Admit::Log
+        ));
+    }
+
+    #[test]
+    fn take_summary_restarts_count() {
+        let mut last = Last(None);
+        assert!(last.take_summary().is_none());
+        admit(&mut last, Level::Warn, "sensor", "no response");
+        assert!(last.take_summary().is_none());
+        admit(&mut last, Level::Warn, "sensor", "no response");
+        assert_eq!(last.take_summary().unwrap().repeated, 1);
+        assert!(last.take_summary().is_none());
+        assert!(matches!(
+            admit(&mut last, Level::Warn, "sensor", "no response"),
+            Admit::Drop
+        ));
+        assert_eq!(last.take_summary().unwrap().repeated, 1);
+    }
+}
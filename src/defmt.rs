@@ -0,0 +1,348 @@
+//! A `defmt` global logger
+//!
+//! [`defmt`](https://defmt.ferrous-systems.com) sends compact, binary log frames, and a host
+//! tool formats them. Enable the `"defmt"` feature to send `defmt` frames over a UART, with either
+//! blocking writes or DMA transfers:
+//!
+//! - [`init_blocking`](fn.init_blocking.html) writes each frame in a critical section, and waits
+//!   until the frame is in the transmit FIFO.
+//! - [`init_dma`](fn.init_dma.html) copies each frame into a circular DMA buffer, and transfers it
+//!   in the background. Call [`poll()`](fn.poll.html) from the DMA channel's interrupt, like you
+//!   would for the [DMA](../dma/index.html) logger.
+//!
+//! Frames logged before `init_blocking()` or `init_dma()` are discarded.
+//!
+//! The `defmt` logger is separate from this crate's `log` loggers. The `log` macros, and the
+//! panic handler, never write through the `defmt` logger, and the `defmt` macros never write
+//! through a `log` logger. Give each logger its own UART.
+//!
+//! # Example
+//!
+//! The example needs `defmt.x`, so it doesn't build as a host doc test.
+//!
+//! ```ignore
+//! use imxrt_hal::{dma::{Buffer, Channel, Circular}, iomuxc::consts::U2, uart::Tx};
+//! # fn uart2_tx() -> Tx<U2> { unimplemented!() }
+//! # fn dma_channel() -> Channel { unimplemented!() }
+//!
+//! #[repr(align(512))]
+//! struct Align(Buffer<[u8; 512]>);
+//! static BUFFER: Align = Align(Buffer::new([0; 512]));
+//!
+//! imxrt_uart_log::defmt::init_dma(
+//!     uart2_tx(),
+//!     dma_channel(),
+//!     Circular::new(&BUFFER.0).unwrap(),
+//! ).unwrap();
+//!
+//! defmt::info!("Hello world! 3 + 2 = {=u32}", 3 + 2);
+//!
+//! // In the DMA channel's interrupt handler...
+//! imxrt_uart_log::defmt::poll();
+//! ```
+//!
+//! # Linking and decoding
+//!
+//! Link your program with `defmt`'s linker script, `defmt.x`. For instance, add
+//! `-C link-arg=-Tdefmt.x` to your rustflags.
+//!
+//! The logger encodes frames with `defmt`'s default encoding. The frames are self-delimiting,
+//! so a decoder recovers from lost bytes at the next frame. To decode the frames on a host, pass
+//! the UART's data, and your program, to `defmt-print`, or any tool that uses `defmt-decoder`:
+//!
+//! ```text
+//! stty -F /dev/ttyUSB0 115200 raw
+//! cat /dev/ttyUSB0 | defmt-print -e target/thumbv7em-none-eabihf/release/my-program
+//! ```
+//!
+//! This crate's tests encode frames with the logger, and decode them with `defmt-decoder`.
+//!
+//! # Implementation
+//!
+//! The logger holds a critical section from the start of a frame to the end of the frame. A
+//! `defmt` macro that preempts another `defmt` macro panics; the critical section prevents that
+//! on a single core.
+//!
+//! If you fill the DMA buffer before a transfer completes, the DMA logger **drops** the bytes
+//! that don't fit. The decoder discards the damaged frame, and decodes the frames that follow.
+
+use crate::{blocking, dma, timeout, SetLoggerError};
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use critical_section::{CriticalSection, Mutex, RestoreState};
+use dma::Poll;
+use imxrt_hal::dma::{Channel, Circular};
+
+/// Where the logger writes encoded frames
+trait Frames {
+    fn write_bytes(&mut self, bytes: &[u8]);
+    fn flush(&mut self);
+}
+
+// There's no allocator to box the DMA state
+#[allow(clippy::large_enum_variant)]
+enum Output {
+    Blocking(blocking::Sink),
+    Dma(dma::Inner),
+}
+
+impl Frames for Output {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        match self {
+            Output::Blocking(uart) => {
+                // Nothing to do if we time out
+                let _ = uart.write_bytes(bytes);
+            }
+            Output::Dma(inner) => {
+                // Data that doesn't fit is dropped
+                inner.write_bytes(bytes);
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        match self {
+            Output::Blocking(uart) => {
+                // Nothing to do if we time out
+                let _ = uart.flush();
+            }
+            Output::Dma(inner) => {
+                // Nothing to do if we time out
+                let _ = timeout::poll(|| Poll::Idle == inner.poll());
+            }
+        }
+    }
+}
+
+struct Logger<O> {
+    output: O,
+    encoder: ::defmt::Encoder,
+}
+
+type Global<O> = Mutex<RefCell<Option<Logger<O>>>>;
+
+/// The logger, if initialized
+static LOGGER: Global<Output> = Mutex::new(RefCell::new(None));
+
+/// `true` between `acquire()` and `release()`
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// The critical section that `acquire()` entered
+///
+/// Only accessed between `acquire()` and `release()`.
+static mut RESTORE: RestoreState = RestoreState::invalid();
+
+/// Run `f` with the logger, if initialized
+///
+/// # Safety
+///
+/// Only call this between `acquire()` and `release()`.
+unsafe fn with_logger<O>(global: &Global<O>, f: impl FnOnce(&mut Logger<O>)) {
+    // Safety: acquire() entered a critical section
+    let cs = CriticalSection::new();
+    if let Some(logger) = global.borrow(cs).borrow_mut().as_mut() {
+        f(logger);
+    }
+}
+
+// The global logger's implementation, for any output. The tests
+// provide their own global logger, which writes to a host buffer.
+
+fn acquire<O: Frames>(global: &Global<O>) {
+    // Safety: release() restores this critical section
+    let restore = unsafe { critical_section::acquire() };
+    if TAKEN.swap(true, Ordering::Relaxed) {
+        panic!("defmt logger taken reentrantly");
+    }
+    // Safety: we're the only context that's between acquire() and release()
+    unsafe {
+        RESTORE = restore;
+        with_logger(global, |logger| {
+            let Logger { output, encoder } = logger;
+            encoder.start_frame(|bytes| output.write_bytes(bytes));
+        });
+    }
+}
+
+unsafe fn flush<O: Frames>(global: &Global<O>) {
+    with_logger(global, |logger| logger.output.flush());
+}
+
+unsafe fn release<O: Frames>(global: &Global<O>) {
+    with_logger(global, |logger| {
+        let Logger { output, encoder } = logger;
+        encoder.end_frame(|bytes| output.write_bytes(bytes));
+    });
+    TAKEN.store(false, Ordering::Relaxed);
+    critical_section::release(RESTORE);
+}
+
+unsafe fn write<O: Frames>(global: &Global<O>, bytes: &[u8]) {
+    with_logger(global, |logger| {
+        let Logger { output, encoder } = logger;
+        encoder.write(bytes, |bytes| output.write_bytes(bytes));
+    });
+}
+
+#[cfg_attr(not(test), ::defmt::global_logger)]
+#[cfg_attr(test, allow(dead_code))]
+struct GlobalLogger;
+
+unsafe impl ::defmt::Logger for GlobalLogger {
+    fn acquire() {
+        acquire(&LOGGER);
+    }
+
+    unsafe fn flush() {
+        flush(&LOGGER);
+    }
+
+    unsafe fn release() {
+        release(&LOGGER);
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        write(&LOGGER, bytes);
+    }
+}
+
+fn init(output: Output) -> Result<(), SetLoggerError> {
+    critical_section::with(move |cs| {
+        let mut logger = LOGGER.borrow(cs).borrow_mut();
+        if logger.is_some() {
+            return Err(SetLoggerError(()));
+        }
+        *logger = Some(Logger {
+            output,
+            encoder: ::defmt::Encoder::new(),
+        });
+        Ok(())
+    })
+}
+
+/// Initialize the `defmt` logger with a UART's transfer half
+///
+/// The logger writes each frame with blocking writes. `tx` should be an `imxrt_hal::uart::Tx`
+/// half, or a [`blocking::Sink`](../blocking/enum.Sink.html).
+///
+/// Returns an error if you've already initialized the `defmt` logger.
+pub fn init_blocking<S>(tx: S) -> Result<(), SetLoggerError>
+where
+    S: Into<blocking::Sink>,
+{
+    init(Output::Blocking(tx.into()))
+}
+
+/// Initialize the `defmt` logger with a UART's transfer half, a DMA channel, and a DMA buffer
+///
+/// The logger transfers frames from `buffer` with `channel`. Enable the channel's interrupt
+/// on completion, and call [`poll()`](fn.poll.html) from the interrupt handler.
+///
+/// Returns an error if you've already initialized the `defmt` logger.
+pub fn init_dma<T>(tx: T, channel: Channel, buffer: Circular<u8>) -> Result<(), SetLoggerError>
+where
+    T: dma::IntoSink,
+{
+    init(Output::Dma(dma::Inner::new(tx, channel, buffer)))
+}
+
+/// Drive the `defmt` logger's DMA transfers
+///
+/// Call `poll()` from the DMA channel's interrupt handler. See [`Poll`](../dma/enum.Poll.html)
+/// for the meaning of the return. If the logger uses blocking writes, or if there is no logger,
+/// `poll()` returns `Poll::Idle`.
+pub fn poll() -> Poll {
+    critical_section::with(|cs| {
+        // A frame can't be in progress here; the frame holds the critical section
+        match LOGGER.borrow(cs).borrow_mut().as_mut() {
+            Some(Logger {
+                output: Output::Dma(inner),
+                ..
+            }) => inner.poll(),
+            _ => Poll::Idle,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::{Frames, Global, Logger};
+    use core::cell::RefCell;
+    use critical_section::Mutex;
+    use defmt_decoder::DecodeError;
+    use std::{string::ToString, vec::Vec};
+
+    impl Frames for Vec<u8> {
+        fn write_bytes(&mut self, bytes: &[u8]) {
+            self.extend_from_slice(bytes);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    static HOST: Global<Vec<u8>> = Mutex::new(RefCell::new(None));
+
+    #[::defmt::global_logger]
+    struct HostLogger;
+
+    unsafe impl ::defmt::Logger for HostLogger {
+        fn acquire() {
+            super::acquire(&HOST);
+        }
+
+        unsafe fn flush() {
+            super::flush(&HOST);
+        }
+
+        unsafe fn release() {
+            super::release(&HOST);
+        }
+
+        unsafe fn write(bytes: &[u8]) {
+            super::write(&HOST, bytes);
+        }
+    }
+
+    /// Decode the frames in `bytes` with this test program's `defmt` table
+    fn decode(bytes: &[u8]) -> Vec<std::string::String> {
+        let elf = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let table = defmt_decoder::Table::parse(&elf).unwrap().unwrap();
+        let mut decoder = table.new_stream_decoder();
+        decoder.received(bytes);
+        let mut messages = Vec::new();
+        loop {
+            match decoder.decode() {
+                Ok(frame) => messages.push(frame.display_message().to_string()),
+                // Like defmt-print, skip damaged frames
+                Err(DecodeError::Malformed) => continue,
+                Err(DecodeError::UnexpectedEof) => return messages,
+            }
+        }
+    }
+
+    #[test]
+    fn decode_frames() {
+        critical_section::with(|cs| {
+            *HOST.borrow_ref_mut(cs) = Some(Logger {
+                output: Vec::new(),
+                encoder: ::defmt::Encoder::new(),
+            })
+        });
+
+        // Without DEFMT_LOG, defmt only keeps errors
+        ::defmt::error!("Hello world! 3 + 2 = {=u32}", 3 + 2);
+        ::defmt::error!("{=str} frame", "second");
+
+        let mut bytes = critical_section::with(|cs| HOST.borrow_ref_mut(cs).take())
+            .unwrap()
+            .output;
+        assert_eq!(decode(&bytes), ["Hello world! 3 + 2 = 5", "second frame"]);
+
+        // Lose part of the first frame. The decoder recovers at the next frame.
+        bytes.drain(..2);
+        assert_eq!(decode(&bytes), ["second frame"]);
+    }
+}
@@ -0,0 +1,314 @@
+//! Dispatches log records to the active logger
+//!
+//! The dispatcher is the only logger that we register with the `log` crate.
+//! The `init()` functions select the logger that receives records, so users
+//! may re-initialize, and switch between, the blocking and DMA loggers.
+
+#[cfg(feature = "async")]
+use crate::asynch;
+#[cfg(feature = "io")]
+use crate::io;
+#[cfg(feature = "lpuart")]
+use crate::lpuart;
+#[cfg(feature = "rtic")]
+use crate::rtic;
+#[cfg(feature = "imxrt-hal")]
+use crate::{blocking, dma, tee};
+use crate::{dedup, early, memory, once::Once, ratelimit, SetLoggerError};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// A logging implementation
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Early,
+    #[cfg(feature = "imxrt-hal")]
+    Blocking,
+    #[cfg(feature = "imxrt-hal")]
+    Dma,
+    #[cfg(feature = "imxrt-hal")]
+    Tee,
+    Memory,
+    #[cfg(feature = "lpuart")]
+    Lpuart,
+    #[cfg(feature = "io")]
+    Io,
+    #[cfg(feature = "async")]
+    Async,
+    #[cfg(feature = "rtic")]
+    Rtic,
+}
+
+const NONE: u8 = 0;
+const EARLY: u8 = 1;
+#[cfg(feature = "imxrt-hal")]
+const BLOCKING: u8 = 2;
+#[cfg(feature = "imxrt-hal")]
+const DMA: u8 = 3;
+#[cfg(feature = "imxrt-hal")]
+const TEE: u8 = 4;
+const MEMORY: u8 = 5;
+#[cfg(feature = "lpuart")]
+const LPUART: u8 = 6;
+#[cfg(feature = "io")]
+const IO: u8 = 7;
+#[cfg(feature = "async")]
+const ASYNC: u8 = 8;
+#[cfg(feature = "rtic")]
+const RTIC: u8 = 9;
+
+/// The backend that receives log records
+static ACTIVE: AtomicU8 = AtomicU8::new(NONE);
+
+/// Registers `DISPATCH` with the `log` crate
+static REGISTRATION: Once = Once::new();
+
+/// Returns the backend that receives log records
+pub fn active() -> Option<Backend> {
+    match ACTIVE.load(Ordering::Acquire) {
+        EARLY => Some(Backend::Early),
+        #[cfg(feature = "imxrt-hal")]
+        BLOCKING => Some(Backend::Blocking),
+        #[cfg(feature = "imxrt-hal")]
+        DMA => Some(Backend::Dma),
+        #[cfg(feature = "imxrt-hal")]
+        TEE => Some(Backend::Tee),
+        MEMORY => Some(Backend::Memory),
+        #[cfg(feature = "lpuart")]
+        LPUART => Some(Backend::Lpuart),
+        #[cfg(feature = "io")]
+        IO => Some(Backend::Io),
+        #[cfg(feature = "async")]
+        ASYNC => Some(Backend::Async),
+        #[cfg(feature = "rtic")]
+        RTIC => Some(Backend::Rtic),
+        _ => None,
+    }
+}
+
+/// Dispatch all log records to `backend`
+///
+/// Registers the dispatcher with the `log` crate if it's not registered. Returns an
+/// error if another logger is registered, or if we preempted a registration.
+pub fn activate(backend: Backend, max_level: ::log::LevelFilter) -> Result<(), SetLoggerError> {
+    activate_with(&REGISTRATION, backend, max_level)
+}
+
+/// Implements [`activate()`](fn.activate.html) with a registration state
+fn activate_with(
+    registration: &Once,
+    backend: Backend,
+    max_level: ::log::LevelFilter,
+) -> Result<(), SetLoggerError> {
+    registration
+        .call_once(|| ::log::set_logger(&DISPATCH))
+        .map_err(|_| SetLoggerError(()))?;
+    ::log::set_max_level(max_level);
+    let active = match backend {
+        Backend::Early => EARLY,
+        #[cfg(feature = "imxrt-hal")]
+        Backend::Blocking => BLOCKING,
+        #[cfg(feature = "imxrt-hal")]
+        Backend::Dma => DMA,
+        #[cfg(feature = "imxrt-hal")]
+        Backend::Tee => TEE,
+        Backend::Memory => MEMORY,
+        #[cfg(feature = "lpuart")]
+        Backend::Lpuart => LPUART,
+        #[cfg(feature = "io")]
+        Backend::Io => IO,
+        #[cfg(feature = "async")]
+        Backend::Async => ASYNC,
+        #[cfg(feature = "rtic")]
+        Backend::Rtic => RTIC,
+    };
+    ACTIVE.store(active, Ordering::Release);
+    Ok(())
+}
+
+/// Write `args` through the active logger, without a level or target
+///
+/// The active logger doesn't filter `args`.
+pub fn print(args: core::fmt::Arguments) {
+    match active() {
+        #[cfg(feature = "imxrt-hal")]
+        Some(Backend::Blocking) => blocking::print(args),
+        #[cfg(feature = "imxrt-hal")]
+        Some(Backend::Dma) => dma::print(args),
+        #[cfg(feature = "imxrt-hal")]
+        Some(Backend::Tee) => tee::print(args),
+        Some(Backend::Memory) => memory::print(args),
+        #[cfg(feature = "lpuart")]
+        Some(Backend::Lpuart) => lpuart::print(args),
+        #[cfg(feature = "io")]
+        Some(Backend::Io) => io::print(args),
+        #[cfg(feature = "async")]
+        Some(Backend::Async) => asynch::print(args),
+        #[cfg(feature = "rtic")]
+        Some(Backend::Rtic) => rtic::print(args),
+        Some(Backend::Early) => early::print(args),
+        None => {}
+    }
+}
+
+/// Write `args` through the active logger, even if another context is writing
+///
+/// Returns `false` if there is no active logger.
+#[cfg(any(feature = "panic-handler", feature = "fault"))]
+pub fn emergency_write(args: core::fmt::Arguments) -> bool {
+    match active() {
+        #[cfg(feature = "imxrt-hal")]
+        Some(Backend::Blocking) => blocking::emergency_write(args),
+        #[cfg(feature = "imxrt-hal")]
+        Some(Backend::Dma) => dma::emergency_write(args),
+        #[cfg(feature = "imxrt-hal")]
+        Some(Backend::Tee) => tee::emergency_write(args),
+        Some(Backend::Memory) => memory::emergency_write(args),
+        #[cfg(feature = "lpuart")]
+        Some(Backend::Lpuart) => lpuart::emergency_write(args),
+        #[cfg(feature = "io")]
+        Some(Backend::Io) => io::emergency_write(args),
+        #[cfg(feature = "async")]
+        Some(Backend::Async) => false,
+        #[cfg(feature = "rtic")]
+        Some(Backend::Rtic) => false,
+        Some(Backend::Early) | None => false,
+    }
+}
+
+struct Dispatch;
+
+static DISPATCH: Dispatch = Dispatch;
+
+impl ::log::Log for Dispatch {
+    fn enabled(&self, metadata: &::log::Metadata) -> bool {
+        match active() {
+            #[cfg(feature = "imxrt-hal")]
+            Some(Backend::Blocking) => blocking::enabled(metadata),
+            #[cfg(feature = "imxrt-hal")]
+            Some(Backend::Dma) => dma::enabled(metadata),
+            #[cfg(feature = "imxrt-hal")]
+            Some(Backend::Tee) => tee::enabled(metadata),
+            Some(Backend::Memory) => memory::enabled(metadata),
+            #[cfg(feature = "lpuart")]
+            Some(Backend::Lpuart) => lpuart::enabled(metadata),
+            #[cfg(feature = "io")]
+            Some(Backend::Io) => io::enabled(metadata),
+            #[cfg(feature = "async")]
+            Some(Backend::Async) => asynch::enabled(metadata),
+            #[cfg(feature = "rtic")]
+            Some(Backend::Rtic) => rtic::enabled(metadata),
+            Some(Backend::Early) => early::enabled(metadata),
+            None => false,
+        }
+    }
+
+    fn log(&self, record: &::log::Record) {
+        // The filters only count records that the active logger would write
+        if (dedup::is_enabled() || ratelimit::is_enabled()) && !self.enabled(record.metadata()) {
+            return;
+        }
+        if !ratelimit::admit(record) || !dedup::admit(record) {
+            return;
+        }
+        match active() {
+            #[cfg(feature = "imxrt-hal")]
+            Some(Backend::Blocking) => blocking::log(record),
+            #[cfg(feature = "imxrt-hal")]
+            Some(Backend::Dma) => dma::log(record),
+            #[cfg(feature = "imxrt-hal")]
+            Some(Backend::Tee) => tee::log(record),
+            Some(Backend::Memory) => memory::log(record),
+            #[cfg(feature = "lpuart")]
+            Some(Backend::Lpuart) => lpuart::log(record),
+            #[cfg(feature = "io")]
+            Some(Backend::Io) => io::log(record),
+            #[cfg(feature = "async")]
+            Some(Backend::Async) => asynch::log(record),
+            #[cfg(feature = "rtic")]
+            Some(Backend::Rtic) => rtic::log(record),
+            Some(Backend::Early) => early::log(record),
+            None => {}
+        }
+    }
+
+    fn flush(&self) {
+        dedup::flush();
+        match active() {
+            #[cfg(feature = "imxrt-hal")]
+            Some(Backend::Blocking) => blocking::flush(),
+            #[cfg(feature = "imxrt-hal")]
+            Some(Backend::Dma) => dma::flush(),
+            #[cfg(feature = "imxrt-hal")]
+            Some(Backend::Tee) => tee::flush(),
+            #[cfg(feature = "lpuart")]
+            Some(Backend::Lpuart) => lpuart::flush(),
+            #[cfg(feature = "io")]
+            Some(Backend::Io) => io::flush(),
+            #[cfg(feature = "async")]
+            Some(Backend::Async) => {}
+            #[cfg(feature = "rtic")]
+            Some(Backend::Rtic) => {}
+            Some(Backend::Early) | Some(Backend::Memory) | None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::{activate, activate_with, active, Backend};
+    use crate::{memory, once::Once, tests::lock_logger, LoggingConfig};
+    use std::boxed::Box;
+
+    fn config() -> LoggingConfig {
+        LoggingConfig {
+            max_level: ::log::LevelFilter::Info,
+            filters: &[],
+        }
+    }
+
+    #[test]
+    fn double_init() {
+        let _lock = lock_logger();
+        let first = Box::leak(Box::new([0u8; 64]));
+        let second = Box::leak(Box::new([0u8; 64]));
+        let first_ptr = first.as_ptr();
+
+        memory::init(first, config()).unwrap();
+        assert!(memory::init(second, config()).is_err());
+        assert!(active() == Some(Backend::Memory));
+
+        // The dispatcher stays registered
+        activate(Backend::Memory, ::log::LevelFilter::Info).unwrap();
+        activate(Backend::Memory, ::log::LevelFilter::Info).unwrap();
+
+        let first = memory::release().unwrap();
+        assert_eq!(first.as_ptr(), first_ptr);
+        memory::init(first, config()).unwrap();
+        assert!(memory::release().is_some());
+    }
+
+    #[test]
+    fn activate_preempts_registration() {
+        let _lock = lock_logger();
+        let before = active();
+
+        // Simulate an interrupt that activates a logger while another
+        // context is registering the dispatcher
+        let registration = Once::new();
+        let mut preempted = None;
+        registration
+            .call_once(|| -> Result<(), ()> {
+                preempted = Some(activate_with(
+                    &registration,
+                    Backend::Early,
+                    ::log::LevelFilter::Info,
+                ));
+                Ok(())
+            })
+            .unwrap();
+
+        assert!(preempted.unwrap().is_err());
+        assert!(active() == before);
+    }
+}
@@ -85,6 +85,41 @@
 //! or more `poll()` calls in between the two calls. The most responsive async logger will call `poll()` in the DMA channel's interrupt
 //! handler, which will run as soon as a transfer completes.
 //!
+//! ## Switching to blocking writes
+//!
+//! If you can no longer service DMA transfers, like in a panic or fault handler, use
+//! [`switch_to_blocking()`](fn.switch_to_blocking.html). The logger cancels the active transfer,
+//! writes all enqueued data with the UART, and continues to log with blocking writes.
+//!
+//! ```no_run
+//! imxrt_uart_log::dma::switch_to_blocking();
+//! log::error!("Written before this call returns");
+//! ```
+//!
+//! ## Blocking writes for severe records
+//!
+//! To guarantee that errors reach the UART, without paying for blocking writes on every
+//! record, use [`set_blocking_level()`](fn.set_blocking_level.html). The logger writes records
+//! at or above that level with blocking writes, and all other records with DMA transfers.
+//!
+//! ```no_run
+//! imxrt_uart_log::dma::set_blocking_level(log::LevelFilter::Error);
+//! log::info!("Transferred with DMA");
+//! log::error!("Written before this call returns");
+//! ```
+//!
+//! Before a blocking write, the logger waits for all enqueued data to transfer, so records
+//! stay in order. The logger waits, and writes, in a critical section. If the transfers don't
+//! finish within the [poll timeout](../blocking/fn.set_poll_timeout.html), the logger drops the
+//! record. The level also applies to the [tee logger's](../tee/index.html) DMA outputs.
+//!
+//! ## Releasing the UART and DMA channel
+//!
+//! Use [`release()`](fn.release.html) to take back the UART transfer half and the DMA channel.
+//! You may reconfigure the peripherals, or hand them to other software, and then call `init()`
+//! again. The logger stays registered with the `log` crate, and it discards messages while it has
+//! no UART.
+//!
 //! # Example
 //!
 //! In this example, we select DMA channel 7 to use for logging transfers. We implement the `DMA7_DMA23` interrupt to
@@ -148,166 +183,417 @@
 
 mod sink;
 mod writer;
-use sink::{IntoSink, Sink};
+pub(crate) use sink::IntoSink;
+use sink::Sink;
 use writer::Writer;
 
-use crate::{Filters, LoggingConfig, SetLoggerError};
-use core::{cell::RefCell, fmt::Write};
-use cortex_m::interrupt::{self, Mutex};
+pub use crate::poll::Poll;
+
+use crate::{
+    blocking,
+    dispatch::{self, Backend},
+    early, timeout, Filters, LoggingConfig, SetLoggerError,
+};
+use core::{
+    cell::RefCell,
+    fmt::{self, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use critical_section::Mutex;
 use imxrt_hal::dma::{Channel, Circular};
 
-struct Inner {
+/// DMA transfer state
+pub(crate) struct Dma {
     sink: Sink,
     /// The buffer transitions into the DMA peripheral when there is an active
     /// transfer. If this is `Some(..)`, we're idle.
     buffer: Option<Circular<u8>>,
 }
 
+/// DMA logging state
+///
+/// The [tee](../tee/index.html) logger uses this to write to DMA outputs.
+pub(crate) enum Inner {
+    /// Logging with DMA transfers
+    Dma(Dma),
+    /// Logging with blocking writes, after a switch
+    Blocking {
+        uart: blocking::Sink,
+        channel: Channel,
+        buffer: Circular<u8>,
+    },
+    /// Switching from DMA to blocking writes
+    ///
+    /// Only observable if the switch panicked.
+    Switching,
+}
+
 struct Logger {
     filters: Filters,
-    inner: Mutex<RefCell<Inner>>,
+    inner: Inner,
 }
 
+/// The logger, if initialized
 static LOGGER: Mutex<RefCell<Option<Logger>>> = Mutex::new(RefCell::new(None));
 
-impl ::log::Log for Logger {
+/// The least severe level that we write with blocking writes,
+/// as a `LevelFilter`. Zero (`Off`) disables blocking writes.
+static BLOCKING_LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns `true` if we write records at `level` with blocking writes
+pub(crate) fn is_blocking(level: ::log::Level) -> bool {
+    level as usize <= BLOCKING_LEVEL.load(Ordering::Relaxed)
+}
+
+impl Logger {
     fn enabled(&self, metadata: &::log::Metadata) -> bool {
         metadata.level() <= ::log::max_level() // The log level is appropriate
             && self.filters.is_enabled(metadata) // The target is in the filter list
     }
+}
 
-    fn flush(&self) { /* Nothing to do */
-    }
+pub(crate) fn enabled(metadata: &::log::Metadata) -> bool {
+    critical_section::with(|cs| {
+        let logger = LOGGER.borrow(cs).borrow();
+        matches!(logger.as_ref(), Some(logger) if logger.enabled(metadata))
+    })
+}
 
-    fn log(&self, record: &::log::Record) {
-        if self.enabled(record.metadata()) {
-            // TODO could perform string interpolation outside of critical section,
-            // at the cost of additional memory usage...
-            interrupt::free(|cs| {
-                let logger = self.inner.borrow(cs);
-                let mut logger = logger.borrow_mut();
-
-                if let Some(mut buffer) = logger.buffer.take() {
-                    // We have the buffer here, so there's not an active transfer
-                    write!(
-                        Writer::Circular(&mut buffer),
-                        "[{} {}]: {}\r\n",
-                        record.level(),
-                        record.target(),
-                        record.args()
-                    )
-                    .expect("never fails");
-                    // Start the transfer
-                    logger.sink.start_transfer(buffer);
-                } else if logger.sink.is_transfer_complete() {
-                    // Transfer is complete. We need to finalize the transfer,
-                    // and re-schedule it here.
-                    let mut buffer = logger.sink.transfer_complete().unwrap();
-                    write!(
-                        Writer::Circular(&mut buffer),
-                        "[{} {}]: {}\r\n",
-                        record.level(),
-                        record.target(),
-                        record.args()
-                    )
-                    .expect("never fails");
-                    logger.sink.start_transfer(buffer);
+pub(crate) fn flush() { /* Nothing to do */
+}
+
+pub(crate) fn log(record: &::log::Record) {
+    // TODO could perform string interpolation outside of critical section,
+    // at the cost of additional memory usage...
+    critical_section::with(|cs| {
+        let mut logger = LOGGER.borrow(cs).borrow_mut();
+        if let Some(logger) = logger.as_mut() {
+            if logger.enabled(record.metadata()) {
+                let write: fn(&mut Inner, fmt::Arguments) = if is_blocking(record.level()) {
+                    Inner::write_blocking
                 } else {
-                    // There's an active transfer; find the buffer in the peripheral,
-                    // and fill it with data
-                    let mut buffer = logger.sink.write_half().unwrap();
-                    write!(
-                        Writer::WriteHalf(&mut buffer),
+                    Inner::write
+                };
+                write(
+                    &mut logger.inner,
+                    format_args!(
                         "[{} {}]: {}\r\n",
                         record.level(),
                         record.target(),
                         record.args()
-                    )
-                    .expect("never fails");
-                }
-            })
+                    ),
+                );
+            }
         }
-    }
+    })
 }
 
-/// A [`poll()`](fn.poll.html)ing result
-///
-/// `Poll` provides insight into the DMA logger's state
-#[derive(Debug, PartialEq, Eq)]
-pub enum Poll {
-    /// There is an active transfer
-    ///
-    /// The next log message will be scheduled after the active transfer
-    /// completes. The transfer will start on either the next call to
-    /// [`poll()`](fn.poll.html), or along with the next written log message.
-    ///
-    /// An `Active` return could mean that
-    ///
-    /// - `poll()` was called while there was an active transfer, and nothing
-    ///   happened.
-    /// - `poll()` was called, and an active transfer is now complete. `poll()` scheduled
-    ///   another transfer after detecting data in the circular buffer.
-    Active,
-    /// There is no active transfer, and the logger is idle
-    ///
-    /// The next log message will be scheduled immediately. An `Idle` result could
-    /// mean that
-    ///
-    /// - `poll()` was called when there was no active transfer.
-    /// - `poll()` was called, and an active transfer is now complete. There was no other
-    ///   log message in the circular buffer, so there's nothing to do.
-    Idle,
+pub(crate) fn print(args: fmt::Arguments) {
+    critical_section::with(|cs| {
+        if let Some(logger) = LOGGER.borrow(cs).borrow_mut().as_mut() {
+            logger.inner.write(args);
+        }
+    })
 }
 
-/// Drives DMA-based logging
-///
-/// You may call this repeatedly to drive the DMA-based logging. Calling `poll()`
-/// can happen in the DMA channel's interrupt handler, or throughout an event loop. `poll()`
-/// runs in a critical section.
-///
-/// If the transfer is not complete, `poll()` does nothing.
-///
-/// # Panics
-///
-/// If you failed to register a logger using [`init()`](fn.init.html), `poll()` panics.
-#[inline]
-pub fn poll() -> Poll {
-    interrupt::free(|cs| {
-        let logger = LOGGER.borrow(cs);
-        let mut logger = logger.borrow_mut();
-        let logger = logger.as_mut().expect("User has registered a logger");
+impl Dma {
+    fn write(&mut self, args: fmt::Arguments) {
+        self.write_with(|writer| writer.write_fmt(args).expect("never fails"));
+    }
 
-        let logger = logger.inner.borrow(cs);
-        let mut logger = logger.borrow_mut();
+    fn write_bytes(&mut self, bytes: &[u8]) -> usize {
+        let mut written = 0;
+        self.write_with(|writer| written = writer.write_bytes(bytes));
+        written
+    }
 
-        if logger.sink.is_transfer_interrupt() {
-            logger.sink.transfer_clear_interrupt();
+    /// Write data into the DMA buffer with `f`, and schedule a transfer
+    fn write_with(&mut self, f: impl FnOnce(&mut Writer)) {
+        if let Some(mut buffer) = self.buffer.take() {
+            // We have the buffer here, so there's not an active transfer
+            f(&mut Writer::Circular(&mut buffer));
+            // Start the transfer
+            self.sink.start_transfer(buffer);
+        } else if self.sink.is_transfer_complete() {
+            // Transfer is complete. We need to finalize the transfer,
+            // and re-schedule it here.
+            let mut buffer = self.sink.transfer_complete().unwrap();
+            f(&mut Writer::Circular(&mut buffer));
+            self.sink.start_transfer(buffer);
+        } else {
+            // There's an active transfer; find the buffer in the peripheral,
+            // and fill it with data
+            let mut buffer = self.sink.write_half().unwrap();
+            f(&mut Writer::WriteHalf(&mut buffer));
         }
+    }
 
-        if logger.sink.is_transfer_complete() {
-            let buffer = logger.sink.transfer_complete().unwrap();
+    fn poll(&mut self) -> Poll {
+        if self.sink.is_transfer_interrupt() {
+            self.sink.transfer_clear_interrupt();
+        }
+
+        if self.sink.is_transfer_complete() {
+            let buffer = self.sink.transfer_complete().unwrap();
             if !buffer.is_empty() {
                 // There's pending data to send
-                logger.sink.start_transfer(buffer);
+                self.sink.start_transfer(buffer);
             } else {
                 // No pending data; wait for next `log()` call
-                logger.buffer = Some(buffer);
+                self.buffer = Some(buffer);
             }
         }
 
-        match &logger.buffer {
+        match &self.buffer {
             Some(_) => Poll::Idle,
             None => Poll::Active,
         }
+    }
+
+    /// Stop the DMA transfer, and release the peripherals
+    ///
+    /// The returned buffer contains all data that was not
+    /// known to be transferred.
+    fn cancel(mut self) -> (blocking::Sink, Channel, Circular<u8>) {
+        if self.sink.is_transfer_interrupt() {
+            self.sink.transfer_clear_interrupt();
+        }
+        let buffer = match self.buffer.take() {
+            Some(buffer) => buffer,
+            None if self.sink.is_transfer_complete() => self.sink.transfer_complete().unwrap(),
+            None => self.sink.transfer_cancel().unwrap(),
+        };
+        let (uart, channel) = self.sink.release();
+        (uart, channel, buffer)
+    }
+}
+
+impl Inner {
+    pub(crate) fn new<T: IntoSink>(tx: T, channel: Channel, buffer: Circular<u8>) -> Self {
+        Inner::Dma(Dma {
+            sink: tx.into_sink(channel),
+            buffer: Some(buffer),
+        })
+    }
+
+    pub(crate) fn write(&mut self, args: fmt::Arguments) {
+        match self {
+            Inner::Dma(dma) => dma.write(args),
+            Inner::Blocking { uart, .. } => {
+                // Nothing to do if we time out
+                let _ = uart.write_fmt(args);
+            }
+            Inner::Switching => {}
+        }
+    }
+
+    /// Wait for all enqueued data to transfer, then write `args` with blocking writes
+    ///
+    /// Returns to DMA transfers after the write. If the transfers don't finish before
+    /// the timeout, the record is dropped.
+    pub(crate) fn write_blocking(&mut self, args: fmt::Arguments) {
+        if let Inner::Dma(_) = self {
+            if let Inner::Dma(mut dma) = core::mem::replace(self, Inner::Switching) {
+                if !timeout::poll(|| Poll::Idle == dma.poll()) {
+                    *self = Inner::Dma(dma);
+                    return;
+                }
+                let (mut uart, channel, buffer) = dma.cancel();
+                // Nothing to do if we time out
+                let _ = uart.write_fmt(args);
+                let _ = uart.flush();
+                *self = Inner::Dma(Dma {
+                    sink: uart.into_sink(channel),
+                    buffer: Some(buffer),
+                });
+            }
+        } else {
+            self.write(args);
+        }
+    }
+
+    /// Returns the number of bytes that the logger accepted
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) -> usize {
+        match self {
+            Inner::Dma(dma) => dma.write_bytes(bytes),
+            Inner::Blocking { uart, .. } => {
+                // Nothing to do if we time out
+                let _ = uart.write_bytes(bytes);
+                bytes.len()
+            }
+            Inner::Switching => 0,
+        }
+    }
+
+    pub(crate) fn poll(&mut self) -> Poll {
+        match self {
+            Inner::Dma(dma) => dma.poll(),
+            Inner::Blocking { .. } | Inner::Switching => Poll::Idle,
+        }
+    }
+
+    /// Wait for all enqueued data to transfer, then release the peripherals
+    ///
+    /// If the transfers don't finish before the timeout, the logger cancels them.
+    /// Returns `None` if the logger panicked while switching to blocking writes.
+    pub(crate) fn release(self) -> Option<(blocking::Sink, Channel, Circular<u8>)> {
+        match self {
+            Inner::Dma(mut dma) => {
+                // Nothing to do if we time out; cancel() stops the transfer
+                let _ = timeout::poll(|| Poll::Idle == dma.poll());
+                Some(dma.cancel())
+            }
+            Inner::Blocking {
+                mut uart,
+                channel,
+                buffer,
+            } => {
+                // Nothing to do if we time out
+                let _ = uart.flush();
+                Some((uart, channel, buffer))
+            }
+            Inner::Switching => None,
+        }
+    }
+
+    pub(crate) fn switch_to_blocking(&mut self) {
+        if let Inner::Dma(_) = self {
+            if let Inner::Dma(dma) = core::mem::replace(self, Inner::Switching) {
+                let (mut uart, channel, mut buffer) = dma.cancel();
+                drain_blocking(&mut uart, &mut buffer);
+                *self = Inner::Blocking {
+                    uart,
+                    channel,
+                    buffer,
+                };
+            }
+        }
+    }
+
+    /// Switch to blocking writes, then write `args` and wait for the write to complete
+    ///
+    /// Returns `false` if the switch panicked.
+    #[cfg(any(feature = "panic-handler", feature = "fault"))]
+    pub(crate) fn emergency_write(&mut self, args: fmt::Arguments) -> bool {
+        self.switch_to_blocking();
+        if let Inner::Blocking { uart, .. } = self {
+            // Nothing to do if we time out
+            let _ = uart.write_fmt(args);
+            let _ = uart.flush();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Write the buffer's contents with blocking writes
+///
+/// `Circular` doesn't expose its storage as slices, so we write the contents in chunks.
+/// If a chunk times out, the sink ends the partial line, and we discard the rest.
+fn drain_blocking(uart: &mut blocking::Sink, buffer: &mut Circular<u8>) {
+    let mut chunk = [0; 32];
+    while !buffer.is_empty() {
+        let mut len = 0;
+        // Pops at most chunk.len() bytes
+        for (slot, byte) in chunk.iter_mut().zip(buffer.drain()) {
+            *slot = byte;
+            len += 1;
+        }
+        if uart.write_bytes(&chunk[..len]).is_err() {
+            buffer.clear();
+        }
+    }
+}
+
+/// Run `f` with the logger's state
+///
+/// Returns `None` if there is no logger, or if we preempted a context that's using
+/// the logger's state.
+fn with_inner<R>(f: impl FnOnce(&mut Inner) -> R) -> Option<R> {
+    critical_section::with(|cs| {
+        let mut logger = LOGGER.borrow(cs).try_borrow_mut().ok()?;
+        let logger = logger.as_mut()?;
+        Some(f(&mut logger.inner))
+    })
+}
+
+/// Drives DMA-based logging
+///
+/// You may call this repeatedly to drive the DMA-based logging. Calling `poll()`
+/// can happen in the DMA channel's interrupt handler, or throughout an event loop. `poll()`
+/// runs in a critical section.
+///
+/// If the transfer is not complete, `poll()` does nothing. If there is no logger, like after
+/// you [`release()`](fn.release.html) the logger, `poll()` returns `Poll::Idle`.
+#[inline]
+pub fn poll() -> Poll {
+    critical_section::with(|cs| match LOGGER.borrow(cs).borrow_mut().as_mut() {
+        Some(logger) => logger.inner.poll(),
+        None => Poll::Idle,
     })
 }
 
+/// Write severe records with blocking writes
+///
+/// The logger writes records at, or more severe than, `level` with blocking writes. It
+/// transfers all other records with DMA. `LevelFilter::Off` (the default) transfers all
+/// records with DMA. The level applies to the DMA logger, and to every DMA
+/// [`Output`](../tee/struct.Output.html) of the tee logger.
+///
+/// See [Blocking writes for severe records](index.html#blocking-writes-for-severe-records)
+/// for more information.
+pub fn set_blocking_level(level: ::log::LevelFilter) {
+    BLOCKING_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Switch the DMA logger to blocking writes
+///
+/// `switch_to_blocking()` cancels any active DMA transfer, and writes all enqueued data
+/// with the UART. Then, the logger writes all subsequent messages like the
+/// [blocking logger](../blocking/index.html), in a critical section. The logger respects
+/// the blocking logger's [timeout](../blocking/fn.set_poll_timeout.html).
+///
+/// Use this when you can no longer service DMA transfers, like in a panic or fault handler.
+/// The switch is permanent. After switching, [`poll()`](fn.poll.html) always returns `Idle`.
+///
+/// Returns `true` if the logger uses blocking writes. Returns `false` if there is no logger,
+/// or if the switch preempted a context that's using the logger.
+///
+/// The DMA controller does not report how much data it sent before cancellation. The logger
+/// may re-send the partial message that was transferring when you called `switch_to_blocking()`.
+pub fn switch_to_blocking() -> bool {
+    with_inner(|inner| {
+        inner.switch_to_blocking();
+        matches!(inner, Inner::Blocking { .. })
+    })
+    .unwrap_or(false)
+}
+
+/// Switch the DMA logger to blocking writes, then write `args` and wait for the write to complete
+///
+/// If we preempted a context that's using the logger, stop the DMA transfer, and write `args`
+/// through the UART's registers. Only call this from a context that never returns to a
+/// preempted context, like a panic handler. Returns `false` if there is no DMA logger.
+#[cfg(any(feature = "panic-handler", feature = "fault"))]
+pub(crate) fn emergency_write(args: fmt::Arguments) -> bool {
+    match with_inner(|inner| inner.emergency_write(args)) {
+        Some(written) => written,
+        // Safety: caller never returns to the context that's using the logger
+        None => unsafe { emergency::write(args) },
+    }
+}
+
 /// Initialize the DMA-based logger with a UART transfer half and a DMA channel
 ///
 /// `tx` should be an `imxrt_hal::uart::Tx` half, obtained by calling `split()` on a
-/// configured `UART` peripheral. Returns an error if you've already called `init()`, or if
+/// configured `UART` peripheral. Returns an error if you've already called `init()`, and
+/// you haven't [`release()`](fn.release.html)d the logger. Also returns an error if
 /// you've already specified a logger through another interface.
 ///
+/// If the other logger is initialized, this logger replaces it. See
+/// [Switching loggers](../index.html#switching-loggers) for more information.
+///
 /// See the [module-level documentation](index.html#example) for an example.
 ///
 /// # 'BYOB' Feature
@@ -336,44 +622,215 @@ pub fn init<T>(
 where
     T: IntoSink,
 {
-    let buffer = {
-        #[cfg(feature = "byob")]
-        {
-            buffer
+    critical_section::with(move |cs| {
+        let logger = LOGGER.borrow(cs);
+        let mut logger = logger.borrow_mut();
+        if logger.is_some() {
+            return Err(SetLoggerError(()));
         }
+
+        dispatch::activate(Backend::Dma, config.max_level)?;
+
         #[cfg(not(feature = "byob"))]
-        {
-            Circular::new(&buffer::BUFFER.0).unwrap()
+        let buffer = buffer::take(cs);
+        #[cfg(any(feature = "panic-handler", feature = "fault"))]
+        let channel_number = channel.channel();
+        let mut inner = Inner::new(tx, channel, buffer);
+        #[cfg(any(feature = "panic-handler", feature = "fault"))]
+        if let Inner::Dma(dma) = &inner {
+            emergency::set(dma.sink.module(), channel_number);
         }
+        early::replay(|bytes| {
+            inner.write_bytes(bytes);
+        });
+        *logger = Some(Logger {
+            inner,
+            filters: Filters(config.filters),
+        });
+        Ok(())
+    })
+}
+
+/// Release the DMA logger's UART and DMA channel
+///
+/// `release()` waits for all enqueued data to transfer, then returns the UART transfer half
+/// and the DMA channel that you supplied to `init()`. If the transfers don't finish within the
+/// [poll timeout](../blocking/fn.set_poll_timeout.html), `release()` cancels them. Use `TryFrom`
+/// to convert the [`Sink`](../blocking/enum.Sink.html) into your `imxrt_hal::uart::Tx` half.
+/// After `release()`,
+/// the logger discards all messages until you call `init()` again. `poll()` returns `Idle`
+/// until you call `init()`, so you may keep calling `poll()` in your DMA channel's interrupt
+/// handler.
+///
+/// Returns `None` if there is no logger.
+///
+/// # 'BYOB' Feature
+///
+/// If `"byob"` is enabled, `release()` also returns the circular DMA buffer that you supplied
+/// to `init()`:
+///
+/// ```ignore
+/// pub fn release() -> Option<(Sink, Channel, Circular<u8>)>
+/// ```
+///
+/// Otherwise, the logger keeps the default DMA buffer for the next call to `init()`.
+#[cfg(not(feature = "byob"))]
+pub fn release() -> Option<(blocking::Sink, Channel)> {
+    let (uart, channel, buffer) = release_logger()?;
+    critical_section::with(|cs| buffer::release(cs, buffer));
+    Some((uart, channel))
+}
+
+/// Release the DMA logger's UART, DMA channel, and DMA buffer
+///
+/// See the documentation for the `release()` function when `"byob"` is disabled.
+#[cfg(feature = "byob")]
+pub fn release() -> Option<(blocking::Sink, Channel, Circular<u8>)> {
+    release_logger()
+}
+
+fn release_logger() -> Option<(blocking::Sink, Channel, Circular<u8>)> {
+    // Wait for enqueued data outside of a critical section, so
+    // we don't block interrupts for the entire transfer...
+    let _ = timeout::poll(|| with_inner(|inner| inner.poll()) != Some(Poll::Active));
+    // ...then wait for anything that was logged in the meantime.
+    let logger = critical_section::with(|cs| LOGGER.borrow(cs).borrow_mut().take())?;
+    #[cfg(any(feature = "panic-handler", feature = "fault"))]
+    emergency::clear();
+    logger.inner.release()
+}
+
+/// The DMA logger's UART and DMA channel registers
+///
+/// An emergency write may preempt a context that's using the logger's state. The preempted
+/// context may hold the DMA buffer, so the emergency write can't use the logger's state.
+/// Instead, it stops the transfer, and writes through the UART's registers.
+#[cfg(any(feature = "panic-handler", feature = "fault"))]
+mod emergency {
+    use crate::blocking;
+    use core::{
+        fmt::{self, Write},
+        sync::atomic::{AtomicU8, Ordering},
     };
+    use imxrt_hal::ral;
 
-    interrupt::free(move |cs| {
-        let logger = LOGGER.borrow(cs);
-        let mut logger = logger.borrow_mut();
-        if logger.is_none() {
-            *logger = Some(Logger {
-                inner: Mutex::new(RefCell::new(Inner {
-                    sink: tx.into_sink(channel),
-                    buffer: Some(buffer),
-                })),
-                filters: Filters(config.filters),
-            })
+    /// The logger's UART number, or 0 if there is no logger
+    static UART: AtomicU8 = AtomicU8::new(0);
+    /// The logger's DMA channel number
+    static CHANNEL: AtomicU8 = AtomicU8::new(0);
+
+    pub fn set(uart: u8, channel: usize) {
+        CHANNEL.store(channel as u8, Ordering::Relaxed);
+        UART.store(uart, Ordering::Release);
+    }
+
+    pub fn clear() {
+        UART.store(0, Ordering::Release);
+    }
+
+    /// Writes to a UART with polled register writes
+    struct Uart(ral::lpuart::Instance);
+
+    impl fmt::Write for Uart {
+        fn write_str(&mut self, string: &str) -> fmt::Result {
+            for &byte in string.as_bytes() {
+                if !blocking::poll(|| ral::read_reg!(ral::lpuart, self.0, STAT, TDRE == TDRE_1)) {
+                    return Err(fmt::Error);
+                }
+                ral::write_reg!(ral::lpuart, self.0, DATA, byte as u32);
+            }
+            Ok(())
         }
+    }
 
-        // Safety: lifetime is static, and we're transmuting lifetimes
-        let logger: &'static Logger = unsafe { core::mem::transmute(logger.as_ref().unwrap()) };
-        ::log::set_logger(logger)
-            .map(|_| ::log::set_max_level(config.max_level))
-            .map_err(From::from)
-    })
+    /// Stop the DMA transfer, then write `args` with polled writes
+    ///
+    /// Returns `false` if there is no logger.
+    ///
+    /// # Safety
+    ///
+    /// The caller must never return to a context that's using the logger.
+    pub unsafe fn write(args: fmt::Arguments) -> bool {
+        let lpuart = match UART.load(Ordering::Acquire) {
+            1 => ral::lpuart::LPUART1::steal(),
+            2 => ral::lpuart::LPUART2::steal(),
+            3 => ral::lpuart::LPUART3::steal(),
+            4 => ral::lpuart::LPUART4::steal(),
+            5 => ral::lpuart::LPUART5::steal(),
+            6 => ral::lpuart::LPUART6::steal(),
+            7 => ral::lpuart::LPUART7::steal(),
+            8 => ral::lpuart::LPUART8::steal(),
+            _ => return false,
+        };
+        let dma = ral::dma0::DMA0::steal();
+        ral::write_reg!(ral::dma0, dma, CERQ, CHANNEL.load(Ordering::Relaxed));
+        ral::modify_reg!(ral::lpuart, lpuart, BAUD, TDMAE: 0);
+
+        let mut uart = Uart(lpuart);
+        // Nothing to do if we time out
+        let _ = uart.write_fmt(args);
+        let _ = blocking::poll(|| ral::read_reg!(ral::lpuart, uart.0, STAT, TC == TC_1));
+        true
+    }
 }
 
 #[cfg(not(feature = "byob"))]
 mod buffer {
-    use imxrt_hal::dma::Buffer;
+    use core::cell::RefCell;
+    use critical_section::{CriticalSection, Mutex};
+    use imxrt_hal::dma::{Buffer, Circular};
 
     #[repr(align(2048))]
     pub struct Alignment(pub Buffer<[u8; 2048]>);
 
     pub static BUFFER: Alignment = Alignment(Buffer::new([0; 2048]));
+
+    /// The default buffer, after a logger releases it
+    static RELEASED: Mutex<RefCell<Option<Circular<u8>>>> = Mutex::new(RefCell::new(None));
+
+    /// Take the default buffer
+    ///
+    /// Only call this when there's no logger.
+    pub fn take(cs: CriticalSection) -> Circular<u8> {
+        RELEASED
+            .borrow(cs)
+            .borrow_mut()
+            .take()
+            .unwrap_or_else(|| Circular::new(&BUFFER.0).unwrap())
+    }
+
+    /// Keep the default buffer for the next logger
+    pub fn release(cs: CriticalSection, mut buffer: Circular<u8>) {
+        buffer.clear();
+        *RELEASED.borrow(cs).borrow_mut() = Some(buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_blocking, poll, set_blocking_level, Poll};
+    use log::{Level, LevelFilter};
+
+    #[test]
+    fn poll_without_logger() {
+        assert_eq!(poll(), Poll::Idle);
+    }
+
+    #[test]
+    fn blocking_level() {
+        let _lock = crate::tests::lock_logger();
+        assert!(!is_blocking(Level::Error));
+
+        set_blocking_level(LevelFilter::Warn);
+        assert!(is_blocking(Level::Error));
+        assert!(is_blocking(Level::Warn));
+        assert!(!is_blocking(Level::Info));
+        assert!(!is_blocking(Level::Trace));
+
+        set_blocking_level(LevelFilter::Trace);
+        assert!(is_blocking(Level::Trace));
+
+        set_blocking_level(LevelFilter::Off);
+        assert!(!is_blocking(Level::Error));
+    }
 }
@@ -1,5 +1,6 @@
 //! DMA sink
 
+use crate::blocking;
 use imxrt_hal::{
     dma::{Channel, Circular, Peripheral, WriteHalf},
     iomuxc,
@@ -21,6 +22,21 @@ pub enum Sink {
 }
 
 impl Sink {
+    /// Returns the UART number
+    #[cfg(any(feature = "panic-handler", feature = "fault"))]
+    pub fn module(&self) -> u8 {
+        match self {
+            Sink::_1(_) => 1,
+            Sink::_2(_) => 2,
+            Sink::_3(_) => 3,
+            Sink::_4(_) => 4,
+            Sink::_5(_) => 5,
+            Sink::_6(_) => 6,
+            Sink::_7(_) => 7,
+            Sink::_8(_) => 8,
+        }
+    }
+
     pub fn is_transfer_interrupt(&self) -> bool {
         match self {
             Sink::_1(periph) => periph.is_transfer_interrupt(),
@@ -102,6 +118,59 @@ impl Sink {
         }
     }
 
+    pub fn transfer_cancel(&mut self) -> Option<Circular<u8>> {
+        match self {
+            Sink::_1(periph) => periph.transfer_cancel(),
+            Sink::_2(periph) => periph.transfer_cancel(),
+            Sink::_3(periph) => periph.transfer_cancel(),
+            Sink::_4(periph) => periph.transfer_cancel(),
+            Sink::_5(periph) => periph.transfer_cancel(),
+            Sink::_6(periph) => periph.transfer_cancel(),
+            Sink::_7(periph) => periph.transfer_cancel(),
+            Sink::_8(periph) => periph.transfer_cancel(),
+        }
+    }
+
+    /// Release the UART and the DMA channel
+    ///
+    /// Callers should ensure that there is no active transfer.
+    pub fn release(self) -> (blocking::Sink, Channel) {
+        match self {
+            Sink::_1(periph) => {
+                let (tx, channel) = periph.transfer_release();
+                (tx.into(), channel)
+            }
+            Sink::_2(periph) => {
+                let (tx, channel) = periph.transfer_release();
+                (tx.into(), channel)
+            }
+            Sink::_3(periph) => {
+                let (tx, channel) = periph.transfer_release();
+                (tx.into(), channel)
+            }
+            Sink::_4(periph) => {
+                let (tx, channel) = periph.transfer_release();
+                (tx.into(), channel)
+            }
+            Sink::_5(periph) => {
+                let (tx, channel) = periph.transfer_release();
+                (tx.into(), channel)
+            }
+            Sink::_6(periph) => {
+                let (tx, channel) = periph.transfer_release();
+                (tx.into(), channel)
+            }
+            Sink::_7(periph) => {
+                let (tx, channel) = periph.transfer_release();
+                (tx.into(), channel)
+            }
+            Sink::_8(periph) => {
+                let (tx, channel) = periph.transfer_release();
+                (tx.into(), channel)
+            }
+        }
+    }
+
     pub fn write_half(&mut self) -> Option<WriteHalf<u8>> {
         match self {
             Sink::_1(periph) => periph.write_half(),
@@ -167,3 +236,18 @@ impl IntoSink for Tx<iomuxc::consts::U8> {
         Sink::_8(Peripheral::new_transfer(self, channel))
     }
 }
+
+impl IntoSink for blocking::Sink {
+    fn into_sink(self, channel: Channel) -> Sink {
+        match self {
+            blocking::Sink::_1(tx) => tx.into_sink(channel),
+            blocking::Sink::_2(tx) => tx.into_sink(channel),
+            blocking::Sink::_3(tx) => tx.into_sink(channel),
+            blocking::Sink::_4(tx) => tx.into_sink(channel),
+            blocking::Sink::_5(tx) => tx.into_sink(channel),
+            blocking::Sink::_6(tx) => tx.into_sink(channel),
+            blocking::Sink::_7(tx) => tx.into_sink(channel),
+            blocking::Sink::_8(tx) => tx.into_sink(channel),
+        }
+    }
+}
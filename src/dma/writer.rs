@@ -8,12 +8,19 @@ pub enum Writer<'a> {
     WriteHalf(&'a mut WriteHalf<'a, u8>),
 }
 
+impl<'a> Writer<'a> {
+    /// Returns the number of bytes written into the buffer
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> usize {
+        match self {
+            Writer::Circular(circular) => circular.insert(bytes.iter().copied()),
+            Writer::WriteHalf(write_half) => write_half.insert(bytes.iter().copied()),
+        }
+    }
+}
+
 impl<'a> fmt::Write for Writer<'a> {
     fn write_str(&mut self, string: &str) -> fmt::Result {
-        match self {
-            Writer::Circular(circular) => circular.insert(string.as_bytes().iter().copied()),
-            Writer::WriteHalf(write_half) => write_half.insert(string.as_bytes().iter().copied()),
-        };
+        self.write_bytes(string.as_bytes());
         Ok(())
     }
 }
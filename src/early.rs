@@ -0,0 +1,113 @@
+//! Capture log records before the UART is ready
+//!
+//! Board bring-up code may log before you can configure a UART or a DMA channel. Call
+//! [`init`](fn.init.html) as early as possible to capture those records in a small static
+//! buffer. The next call to [`blocking::init()`](../blocking/fn.init.html) or
+//! [`dma::init()`](../dma/fn.init.html) writes the captured records before any other record.
+//!
+//! ```no_run
+//! use imxrt_uart_log::LoggingConfig;
+//! # fn uart2_tx() -> imxrt_hal::uart::Tx<imxrt_hal::iomuxc::consts::U2> { unimplemented!() }
+//!
+//! imxrt_uart_log::early::init(LoggingConfig::default()).unwrap();
+//! log::info!("Configuring clocks...");
+//! // Configure clocks, then a UART...
+//! imxrt_uart_log::blocking::init(uart2_tx(), LoggingConfig::default()).unwrap();
+//! // The logger wrote "[INFO ...]: Configuring clocks..."
+//! ```
+//!
+//! The buffer holds [`CAPACITY`](constant.CAPACITY.html) bytes of formatted records. If
+//! you log more than that before `init()`, the buffer keeps the newest records, and
+//! discards the oldest records. The `config` that you supply to `early::init()` filters
+//! the captured records; the `config` that you supply to the UART logger does not.
+
+use crate::{
+    dispatch::{self, Backend},
+    lock::TryLock,
+    ring::Records,
+    Filters, LoggingConfig, SetLoggerError,
+};
+use core::fmt::{self, Write};
+
+/// The number of bytes that the early logger captures
+pub const CAPACITY: usize = 1024;
+
+struct Early {
+    records: Records<[u8; CAPACITY]>,
+    filters: Filters,
+}
+
+static EARLY: TryLock<Early> = TryLock::new(Early {
+    records: Records::new([0; CAPACITY]),
+    filters: Filters(&[]),
+});
+
+pub(crate) fn enabled(metadata: &::log::Metadata) -> bool {
+    metadata.level() <= ::log::max_level()
+        && critical_section::with(
+            |_| matches!(EARLY.try_lock(), Some(early) if early.filters.is_enabled(metadata)),
+        )
+}
+
+pub(crate) fn log(record: &::log::Record) {
+    if record.level() > ::log::max_level() {
+        return;
+    }
+    critical_section::with(|_| {
+        if let Some(mut early) = EARLY.try_lock() {
+            if early.filters.is_enabled(record.metadata()) {
+                // Never fails
+                let _ = write!(
+                    early.records,
+                    "[{} {}]: {}\r\n",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+            }
+        }
+    })
+}
+
+pub(crate) fn print(args: fmt::Arguments) {
+    critical_section::with(|_| {
+        if let Some(mut early) = EARLY.try_lock() {
+            // Never fails
+            let _ = early.records.write_fmt(args);
+        }
+    })
+}
+
+/// Pass all captured records to `write`, then discard them
+///
+/// Call this in a critical section, while initializing a logger.
+pub(crate) fn replay(mut write: impl FnMut(&[u8])) {
+    if let Some(mut early) = EARLY.try_lock() {
+        let ring = early.records.complete();
+        if ring.is_empty() {
+            return;
+        }
+        let (first, second) = ring.as_slices();
+        write(first);
+        write(second);
+        ring.clear();
+    }
+}
+
+/// Capture log records until you initialize a UART logger
+///
+/// Returns an error if you've already initialized a logger, or if you've already
+/// specified a logger through another interface.
+///
+/// See the [module-level documentation](index.html) for more information.
+pub fn init(config: LoggingConfig) -> Result<(), SetLoggerError> {
+    critical_section::with(|_| {
+        let mut early = EARLY.try_lock().ok_or(SetLoggerError(()))?;
+        if dispatch::active().is_some() {
+            return Err(SetLoggerError(()));
+        }
+        dispatch::activate(Backend::Early, config.max_level)?;
+        early.filters = Filters(config.filters);
+        Ok(())
+    })
+}
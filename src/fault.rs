@@ -0,0 +1,195 @@
+//! Diagnostics for fault handlers
+//!
+//! Enable the `"fault"` feature to use this module. Call [`dump`](fn.dump.html) from your
+//! `HardFault` handler to write the stacked exception frame, the fault status registers,
+//! and a decoded fault reason through the initialized logger.
+//!
+//! This module doesn't depend on `cortex-m-rt`, so it works with any runtime version. Its
+//! [`ExceptionFrame`](struct.ExceptionFrame.html) has the same layout as `cortex-m-rt`'s
+//! `ExceptionFrame`, so you may cast a reference from one to the other:
+//!
+//! ```ignore
+//! use cortex_m_rt::{exception, ExceptionFrame};
+//! use imxrt_uart_log::fault;
+//!
+//! #[exception]
+//! unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+//!     // Safety: both types describe the same stacked registers
+//!     let frame = &*(frame as *const ExceptionFrame as *const fault::ExceptionFrame);
+//!     fault::dump(frame)
+//! }
+//! ```
+//!
+//! `dump()` writes like the [panic handler](../index.html#panic-handler): the blocking logger
+//! writes even if the fault preempted another writer, and the DMA logger switches to blocking
+//! writes. Since `dump()` takes the logger from the context that it preempted, it never returns
+//! to that context. `dump()` disables interrupts, and waits for a reset.
+
+use core::fmt;
+use cortex_m::peripheral::SCB;
+
+/// Registers stacked by the processor on exception entry
+///
+/// The layout matches the basic frame that the processor pushes onto the stack, and
+/// `cortex-m-rt`'s `ExceptionFrame`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ExceptionFrame {
+    pub r0: u32,
+    pub r1: u32,
+    pub r2: u32,
+    pub r3: u32,
+    pub r12: u32,
+    pub lr: u32,
+    pub pc: u32,
+    pub xpsr: u32,
+}
+
+/// Fault status register bits, and their descriptions
+///
+/// Bit positions are relative to the CFSR, or the HFSR.
+const CFSR_REASONS: [(u32, &str); 19] = [
+    (0, "instruction access violation"),
+    (1, "data access violation"),
+    (3, "memory management fault on unstacking"),
+    (4, "memory management fault on stacking"),
+    (
+        5,
+        "memory management fault during lazy FP state preservation",
+    ),
+    (8, "instruction bus error"),
+    (9, "precise data bus error"),
+    (10, "imprecise data bus error"),
+    (11, "bus fault on unstacking"),
+    (12, "bus fault on stacking"),
+    (13, "bus fault during lazy FP state preservation"),
+    (16, "undefined instruction"),
+    (17, "invalid state"),
+    (18, "invalid PC load"),
+    (19, "no coprocessor"),
+    (24, "unaligned access"),
+    (25, "divide by zero"),
+    // Address valid bits; not reasons, but useful to know
+    (7, "MMFAR holds the faulting address"),
+    (15, "BFAR holds the faulting address"),
+];
+
+const HFSR_REASONS: [(u32, &str); 3] = [
+    (1, "bus fault on vector table read"),
+    (30, "escalated from a configurable fault"),
+    (31, "debug event"),
+];
+
+/// Returns the descriptions for all bits set in `cfsr` and `hfsr`
+fn reasons(cfsr: u32, hfsr: u32) -> impl Iterator<Item = &'static str> {
+    let cfsr = CFSR_REASONS
+        .iter()
+        .filter(move |(bit, _)| cfsr & (1 << bit) != 0);
+    let hfsr = HFSR_REASONS
+        .iter()
+        .filter(move |(bit, _)| hfsr & (1 << bit) != 0);
+    hfsr.chain(cfsr).map(|(_, reason)| *reason)
+}
+
+/// Fault status registers
+struct Status {
+    cfsr: u32,
+    hfsr: u32,
+    mmfar: u32,
+    bfar: u32,
+}
+
+impl Status {
+    fn read() -> Self {
+        // Safety: atomic reads of fault status registers
+        let scb = unsafe { &*SCB::ptr() };
+        Status {
+            cfsr: scb.cfsr.read(),
+            hfsr: scb.hfsr.read(),
+            mmfar: scb.mmfar.read(),
+            bfar: scb.bfar.read(),
+        }
+    }
+}
+
+/// The fault report
+struct Report<'a> {
+    frame: &'a ExceptionFrame,
+    status: Status,
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frame = self.frame;
+        let status = &self.status;
+        write!(f, "[FAULT]: HardFault\r\n")?;
+        write!(
+            f,
+            "[FAULT]: R0={:#010x} R1={:#010x} R2={:#010x} R3={:#010x}\r\n",
+            frame.r0, frame.r1, frame.r2, frame.r3
+        )?;
+        write!(
+            f,
+            "[FAULT]: R12={:#010x} LR={:#010x} PC={:#010x} xPSR={:#010x}\r\n",
+            frame.r12, frame.lr, frame.pc, frame.xpsr
+        )?;
+        write!(
+            f,
+            "[FAULT]: CFSR={:#010x} HFSR={:#010x} MMFAR={:#010x} BFAR={:#010x}\r\n",
+            status.cfsr, status.hfsr, status.mmfar, status.bfar
+        )?;
+        for reason in reasons(status.cfsr, status.hfsr) {
+            write!(f, "[FAULT]: {}\r\n", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a fault report through the initialized logger
+///
+/// The report includes the stacked exception frame, the CFSR, HFSR, MMFAR and BFAR
+/// registers, and the decoded fault reasons. If there is no logger, `dump()` writes nothing.
+///
+/// `dump()` never returns. After it writes the report, it waits for a reset, or a debugger,
+/// with interrupts disabled.
+///
+/// See the [module-level documentation](index.html) for an example.
+pub fn dump(frame: &ExceptionFrame) -> ! {
+    cortex_m::interrupt::disable();
+
+    let report = Report {
+        frame,
+        status: Status::read(),
+    };
+    crate::dispatch::emergency_write(format_args!("{}", report));
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reasons, ExceptionFrame};
+
+    #[test]
+    fn no_reasons() {
+        assert_eq!(reasons(0, 0).count(), 0);
+    }
+
+    #[test]
+    fn forced_precise_bus_fault() {
+        let cfsr = (1 << 9) | (1 << 15);
+        let hfsr = 1 << 30;
+        let mut reasons = reasons(cfsr, hfsr);
+        assert_eq!(reasons.next(), Some("escalated from a configurable fault"));
+        assert_eq!(reasons.next(), Some("precise data bus error"));
+        assert_eq!(reasons.next(), Some("BFAR holds the faulting address"));
+        assert_eq!(reasons.next(), None);
+    }
+
+    #[test]
+    fn exception_frame_layout() {
+        assert_eq!(core::mem::size_of::<ExceptionFrame>(), 8 * 4);
+        assert_eq!(core::mem::align_of::<ExceptionFrame>(), 4);
+    }
+}
@@ -3,7 +3,23 @@
 /// Filter log messages by module name (`&'static str`) to a log level
 ///
 /// - if the level is `None`, log at all levels from the module (subject to the max log level)
-/// - if the level is not `None`, that will be the base log level for the module
+/// - if the level is `Some(LevelFilter::Off)`, never log from the module, or its submodules
+/// - otherwise, that will be the base log level for the module
+///
+/// An entry with `Some(LevelFilter::Off)` is a *deny* entry. All other entries are *allow*
+/// entries. If there's at least one allow entry, the logger only logs the modules that you
+/// allow. If there are only deny entries, the logger logs all modules except the denied
+/// modules. Deny entries take precedence over allow entries.
+///
+/// Allow entries match the target exactly, so allowing `usb` doesn't allow `usb::bus`. That's
+/// how allow entries behaved before there were deny entries, and it lets you allow a module
+/// without its noisy submodules. Deny entries match the target, and every target that starts
+/// with the target and `::`. For instance, `("usb", Some(LevelFilter::Off))` denies both `usb`
+/// and `usb::bus`, so one entry silences a module and all of its submodules.
+///
+/// The module name `"*"` matches every target that doesn't have an allow entry. Use it to set
+/// the level for all other modules. A `"*"` entry is never a deny entry; `("*",
+/// Some(LevelFilter::Off))` only logs the modules that you allow.
 ///
 /// # Example
 ///
@@ -17,28 +33,61 @@
 ///     // Writes only Error- and Warn-level messages from the 'spi' module
 ///     ("spi", Some(LevelFilter::Warn)),
 /// ];
+///
+/// // Writes messages from all modules, except the 'usb' module and its submodules
+/// static ALL_BUT_USB: &'static [Filter] = &[("usb", Some(LevelFilter::Off))];
+///
+/// // Writes Info-level messages from all modules, except the 'usb' module and its
+/// // submodules. Writes Debug-level messages from the 'spi' module.
+/// static INFO_BUT_USB: &'static [Filter] = &[
+///     ("*", Some(LevelFilter::Info)),
+///     ("usb", Some(LevelFilter::Off)),
+///     ("spi", Some(LevelFilter::Debug)),
+/// ];
 /// ```
 pub type Filter = (&'static str, Option<::log::LevelFilter>);
 
+/// Returns `true` if `target` is `module`, or one of its submodules
+fn in_module(target: &str, module: &str) -> bool {
+    target.starts_with(module)
+        && (target.len() == module.len() || target[module.len()..].starts_with("::"))
+}
+
+/// Matches all targets that don't have an allow entry
+const WILDCARD: &str = "*";
+
+fn is_deny(&(module, level): &Filter) -> bool {
+    module != WILDCARD && level == Some(::log::LevelFilter::Off)
+}
+
 /// Filters for enabling logs
 pub struct Filters(pub &'static [Filter]);
 
 impl Filters {
     /// Returns `true` if, based on this metadata, logging should be enabled
     ///
-    /// `is_enabled()` considers the permitted modules and log levels for those modules.
+    /// `is_enabled()` considers the denied modules, the permitted modules, and log levels
+    /// for those modules.
     pub fn is_enabled(&self, metadata: &::log::Metadata) -> bool {
-        if self.0.is_empty() {
-            true
-        } else if let Some(idx) = self
+        let target = metadata.target();
+        if self
             .0
             .iter()
-            .position(|&(target, _)| target == metadata.target())
+            .any(|filter| is_deny(filter) && in_module(target, filter.0))
         {
-            let (_, lvl) = self.0[idx];
-            lvl.is_none() || lvl.filter(|lvl| metadata.level() <= *lvl).is_some()
-        } else {
-            false
+            return false;
+        }
+
+        let mut allowed = self.0.iter().filter(|filter| !is_deny(filter));
+        let level = allowed
+            .clone()
+            .find(|&&(module, _)| module == target)
+            .or_else(|| allowed.clone().find(|&&(module, _)| module == WILDCARD));
+        match level {
+            Some(&(_, lvl)) => {
+                lvl.is_none() || lvl.filter(|lvl| metadata.level() <= *lvl).is_some()
+            }
+            None => allowed.next().is_none(),
         }
     }
 }
@@ -113,4 +162,68 @@ mod tests {
             });
         assert!(filters.is_enabled(&metadata(Level::Error, "barbaz")));
     }
+
+    #[test]
+    fn deny_only() {
+        let filters = Filters(&[("usb", Some(LevelFilter::Off))]);
+        ALL_LEVELS.iter().for_each(|level| {
+            assert!(filters.is_enabled(&metadata(*level, "foobar")));
+            assert!(filters.is_enabled(&metadata(*level, "usbd")));
+            assert!(!filters.is_enabled(&metadata(*level, "usb")));
+            assert!(!filters.is_enabled(&metadata(*level, "usb::bus")));
+        });
+    }
+
+    #[test]
+    fn deny_takes_precedence() {
+        let filters = Filters(&[
+            ("usb::bus", None),
+            ("usb", Some(LevelFilter::Off)),
+            ("foobar", Some(LevelFilter::Info)),
+        ]);
+        ALL_LEVELS.iter().for_each(|level| {
+            assert!(!filters.is_enabled(&metadata(*level, "usb::bus")));
+            assert!(!filters.is_enabled(&metadata(*level, "barbaz")));
+        });
+        assert!(filters.is_enabled(&metadata(Level::Info, "foobar")));
+        assert!(!filters.is_enabled(&metadata(Level::Debug, "foobar")));
+    }
+
+    #[test]
+    fn allow_is_exact() {
+        let filters = Filters(&[("usb", None)]);
+        ALL_LEVELS.iter().for_each(|level| {
+            assert!(filters.is_enabled(&metadata(*level, "usb")));
+            assert!(!filters.is_enabled(&metadata(*level, "usb::bus")));
+        });
+    }
+
+    #[test]
+    fn wildcard() {
+        let filters = Filters(&[
+            ("*", Some(LevelFilter::Info)),
+            ("usb", Some(LevelFilter::Off)),
+            ("spi", Some(LevelFilter::Debug)),
+        ]);
+        assert!(filters.is_enabled(&metadata(Level::Info, "foobar")));
+        assert!(!filters.is_enabled(&metadata(Level::Debug, "foobar")));
+        assert!(filters.is_enabled(&metadata(Level::Debug, "spi")));
+        assert!(!filters.is_enabled(&metadata(Level::Trace, "spi")));
+        // The wildcard matches submodules that don't have an allow entry
+        assert!(filters.is_enabled(&metadata(Level::Info, "spi::dma")));
+        assert!(!filters.is_enabled(&metadata(Level::Debug, "spi::dma")));
+        ALL_LEVELS.iter().for_each(|level| {
+            assert!(!filters.is_enabled(&metadata(*level, "usb")));
+            assert!(!filters.is_enabled(&metadata(*level, "usb::bus")));
+        });
+    }
+
+    #[test]
+    fn wildcard_off() {
+        let filters = Filters(&[("*", Some(LevelFilter::Off)), ("spi", None)]);
+        ALL_LEVELS.iter().for_each(|level| {
+            assert!(filters.is_enabled(&metadata(*level, "spi")));
+            assert!(!filters.is_enabled(&metadata(*level, "foobar")));
+        });
+    }
 }
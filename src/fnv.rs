@@ -0,0 +1,67 @@
+//! FNV-1a hashes that don't need an allocator
+
+use core::fmt;
+
+const OFFSET_BASIS: u32 = 0x811c_9dc5;
+const PRIME: u32 = 0x0100_0193;
+
+/// A 32-bit FNV-1a hash
+pub struct Fnv(u32);
+
+impl Fnv {
+    pub const fn new() -> Self {
+        Fnv(OFFSET_BASIS)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u32::from(byte)).wrapping_mul(PRIME);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Hash formatted text, without buffering it
+impl fmt::Write for Fnv {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.write(string.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fnv;
+    use core::fmt::Write;
+
+    fn hash(bytes: &[u8]) -> u32 {
+        let mut fnv = Fnv::new();
+        fnv.write(bytes);
+        fnv.finish()
+    }
+
+    #[test]
+    fn known_hashes() {
+        assert_eq!(hash(b""), 0x811c_9dc5);
+        assert_eq!(hash(b"a"), 0xe40c_292c);
+        assert_eq!(hash(b"foobar"), 0xbf9c_f968);
+    }
+
+    #[test]
+    fn split_writes() {
+        let mut fnv = Fnv::new();
+        fnv.write(b"foo");
+        fnv.write(b"bar");
+        assert_eq!(fnv.finish(), hash(b"foobar"));
+    }
+
+    #[test]
+    fn formatted_text() {
+        let mut fnv = Fnv::new();
+        write!(fnv, "foo{}", 42).unwrap();
+        assert_eq!(fnv.finish(), hash(b"foo42"));
+    }
+}
@@ -0,0 +1,260 @@
+//! Hex dumps of byte buffers
+//!
+//! [`HexDump`](struct.HexDump.html) formats a byte buffer like `hexdump -C`: each line
+//! has an offset, up to 16 bytes in hex, and the same bytes as ASCII. Bytes that aren't
+//! printable ASCII show as `.`.
+//!
+//! ```text
+//! 00000000  68 65 6c 6c 6f 20 77 6f  72 6c 64 20 68 65 6c 6c  |hello world hell|
+//! 00000010  6f 00 01 ff                                       |o...|
+//! ```
+//!
+//! To log a buffer, use [`log_hex!`](../macro.log_hex.html). It logs one record per line, so
+//! each line has the logger's level, target, and line terminator. You may also format a
+//! `HexDump` in any `log` macro. It separates lines with `"\r\n"`, the same line terminator
+//! as a log record.
+//!
+//! ```
+//! use imxrt_uart_log::{hexdump::HexDump, log_hex};
+//!
+//! let frame = [0x68, 0x65, 0x6c, 0x6c, 0x6f];
+//! log_hex!(log::Level::Debug, &frame);
+//! log_hex!(target: "i2c", log::Level::Trace, &frame);
+//! log_hex!(target: "i2c", width: 120, log::Level::Trace, &frame);
+//! log::debug!("I2C frame:\r\n{}", HexDump::new(&frame));
+//! ```
+//!
+//! # Line width
+//!
+//! A `HexDump` line with 16 bytes is 78 characters. Use
+//! [`with_width()`](struct.HexDump.html#method.with_width) to fit each line in fewer, or more,
+//! characters. The hex dump shows as many bytes per line as fit, rounded down to a multiple of
+//! 8. Each line shows at least one byte, so a line may exceed a very small width.
+//!
+//! `log_hex!` fits each record, including its `[LEVEL target]: ` prefix, in
+//! [`DEFAULT_WIDTH`](constant.DEFAULT_WIDTH.html) characters. Supply a `width` to change the
+//! width. The width doesn't include the line terminator.
+
+use core::{
+    fmt::{self, Write},
+    slice::Chunks,
+};
+
+/// The most bytes in each line
+const MAX_BYTES_PER_LINE: usize = 16;
+
+/// The number of bytes in each hex group
+const GROUP: usize = 8;
+
+/// The width of a `log_hex!` record, unless you supply a width
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Returns the number of characters in a line that shows `bytes` bytes
+const fn line_width(bytes: usize) -> usize {
+    // Offset and spaces, hex, group separators, and the ASCII between bars
+    9 + 3 * bytes + bytes.div_ceil(GROUP) + 2 + bytes + 1
+}
+
+/// Returns the number of bytes in each line that fits in `width` characters
+fn bytes_per_line(width: usize) -> usize {
+    let bytes = (1..=MAX_BYTES_PER_LINE)
+        .rev()
+        .find(|&bytes| line_width(bytes) <= width)
+        .unwrap_or(1);
+    if bytes >= GROUP {
+        bytes - bytes % GROUP
+    } else {
+        bytes
+    }
+}
+
+/// Returns the width of a hex dump line in a `log_hex!` record
+///
+/// Subtracts the record's `[LEVEL target]: ` prefix from `width`.
+#[doc(hidden)]
+pub fn _record_width(level: ::log::Level, target: &str, width: usize) -> usize {
+    width.saturating_sub(level.as_str().len() + target.len() + 5)
+}
+
+/// Formats a byte buffer as lines of offsets, hex, and ASCII
+///
+/// See the [module-level documentation](index.html) for more information.
+#[derive(Clone, Copy)]
+pub struct HexDump<'a> {
+    bytes: &'a [u8],
+    bytes_per_line: usize,
+}
+
+impl<'a> HexDump<'a> {
+    /// Create a hex dump of `bytes`, with 16 bytes in each line
+    pub const fn new(bytes: &'a [u8]) -> Self {
+        HexDump {
+            bytes,
+            bytes_per_line: MAX_BYTES_PER_LINE,
+        }
+    }
+
+    /// Fit each line in `width` characters
+    ///
+    /// See [Line width](index.html#line-width) for more information.
+    pub fn with_width(self, width: usize) -> Self {
+        HexDump {
+            bytes_per_line: bytes_per_line(width),
+            ..self
+        }
+    }
+
+    /// Returns the lines of the hex dump
+    pub fn lines(&self) -> Lines<'a> {
+        Lines {
+            chunks: self.bytes.chunks(self.bytes_per_line),
+            offset: 0,
+            bytes_per_line: self.bytes_per_line,
+        }
+    }
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, line) in self.lines().enumerate() {
+            if idx > 0 {
+                f.write_str("\r\n")?;
+            }
+            write!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the lines of a [`HexDump`](struct.HexDump.html)
+pub struct Lines<'a> {
+    chunks: Chunks<'a, u8>,
+    offset: usize,
+    bytes_per_line: usize,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = Line<'a>;
+    fn next(&mut self) -> Option<Line<'a>> {
+        let bytes = self.chunks.next()?;
+        let line = Line {
+            offset: self.offset,
+            bytes,
+            bytes_per_line: self.bytes_per_line,
+        };
+        self.offset += bytes.len();
+        Some(line)
+    }
+}
+
+/// One line of a [`HexDump`](struct.HexDump.html), without a line terminator
+pub struct Line<'a> {
+    offset: usize,
+    bytes: &'a [u8],
+    bytes_per_line: usize,
+}
+
+impl fmt::Display for Line<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08x} ", self.offset)?;
+        for idx in 0..self.bytes_per_line {
+            if idx % GROUP == 0 {
+                f.write_char(' ')?;
+            }
+            match self.bytes.get(idx) {
+                Some(byte) => write!(f, "{:02x} ", byte)?,
+                None => f.write_str("   ")?,
+            }
+        }
+        f.write_str(" |")?;
+        for &byte in self.bytes {
+            let printable = byte.is_ascii_graphic() || byte == b' ';
+            f.write_char(if printable { byte as char } else { '.' })?;
+        }
+        f.write_char('|')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::{_record_width, HexDump};
+    use std::format;
+
+    #[test]
+    fn empty() {
+        assert_eq!(format!("{}", HexDump::new(&[])), "");
+        assert_eq!(HexDump::new(&[]).lines().count(), 0);
+    }
+
+    #[test]
+    fn partial_line() {
+        assert_eq!(
+            format!("{}", HexDump::new(b"hello\n")),
+            "00000000  68 65 6c 6c 6f 0a                                 |hello.|"
+        );
+    }
+
+    #[test]
+    fn multiple_lines() {
+        assert_eq!(
+            format!("{}", HexDump::new(b"hello world hello\x00\x01\xff")),
+            "00000000  68 65 6c 6c 6f 20 77 6f  72 6c 64 20 68 65 6c 6c  |hello world hell|\r\n\
+             00000010  6f 00 01 ff                                       |o...|"
+        );
+    }
+
+    #[test]
+    fn full_lines() {
+        let bytes = [0x7Fu8; 32];
+        let dump = HexDump::new(&bytes);
+        assert_eq!(dump.lines().count(), 2);
+        for line in dump.lines() {
+            let line = format!("{}", line);
+            assert_eq!(line.len(), 78);
+            assert!(line.ends_with("|................|"));
+        }
+    }
+
+    #[test]
+    fn widths() {
+        let bytes = [0x7Fu8; 40];
+        for (width, per_line) in [
+            (78, 16),
+            (200, 16),
+            (77, 8),
+            (45, 8),
+            (44, 7),
+            (17, 1),
+            (0, 1),
+        ] {
+            let dump = HexDump::new(&bytes).with_width(width);
+            let line = format!("{}", dump.lines().next().unwrap());
+            assert_eq!(line.matches("7f").count(), per_line, "width {}", width);
+            if per_line > 1 {
+                assert!(line.len() <= width, "width {}", width);
+            }
+        }
+    }
+
+    #[test]
+    fn narrow_partial_line() {
+        assert_eq!(
+            format!("{}", HexDump::new(b"hello world").with_width(45)),
+            "00000000  68 65 6c 6c 6f 20 77 6f  |hello wo|\r\n\
+             00000008  72 6c 64                 |rld|"
+        );
+    }
+
+    #[test]
+    fn record_width() {
+        let prefix = "[DEBUG i2c]: ";
+        let width = _record_width(log::Level::Debug, "i2c", 80);
+        assert_eq!(width, 80 - prefix.len());
+        let bytes = [0u8; 16];
+        for line in HexDump::new(&bytes).with_width(width).lines() {
+            assert!(prefix.len() + format!("{}", line).len() <= 80);
+        }
+        assert_eq!(_record_width(log::Level::Debug, "i2c", 4), 0);
+    }
+}
@@ -0,0 +1,325 @@
+//! Logging through any `embedded-io` writer
+//!
+//! The io logger blocks while it writes records to an `embedded_io::Write` implementation.
+//! It doesn't depend on a UART, so you may log over any transport, like USB serial, an
+//! `imxrt-hal` 0.5 `Lpuart`, or a `std` writer in host tests. Enable the `"io"` feature to
+//! use this module.
+//!
+//! To log through an `embedded-hal` 1.0 serial port, wrap the port in a
+//! [`Serial`](struct.Serial.html). `Serial` adapts an `embedded_hal_nb::serial::Write`
+//! implementation for `embedded_io::Write`.
+//!
+//! ```no_run
+//! use imxrt_uart_log::io::Serial;
+//! # struct Port;
+//! # impl embedded_hal_nb::serial::ErrorType for Port { type Error = core::convert::Infallible; }
+//! # impl embedded_hal_nb::serial::Write for Port {
+//! #     fn write(&mut self, _: u8) -> embedded_hal_nb::nb::Result<(), Self::Error> { Ok(()) }
+//! #     fn flush(&mut self) -> embedded_hal_nb::nb::Result<(), Self::Error> { Ok(()) }
+//! # }
+//! # fn serial_port() -> Port { Port }
+//!
+//! let serial = cortex_m::singleton!(: Serial<Port> = Serial(serial_port())).unwrap();
+//! imxrt_uart_log::io::init(serial, Default::default()).unwrap();
+//!
+//! log::info!("Written through embedded-hal");
+//! ```
+//!
+//! # Implementation
+//!
+//! The writer decides how long the logger blocks. The logger writes with interrupts enabled,
+//! and it serializes writers with a lock that never blocks, like a
+//! [preemptible](../blocking/index.html#preemptible-logging) blocking logger. If an interrupt
+//! preempts a writer, and the interrupt tries to log, the logger drops the interrupt's
+//! message. Use [`dropped`](fn.dropped.html) to learn how many messages were dropped.
+//!
+//! The logger keeps the writer until you [`release()`](fn.release.html) it.
+
+use crate::{
+    dispatch::{self, Backend},
+    early,
+    lock::TryLock,
+    Filters, LoggingConfig, SetLoggerError,
+};
+use core::{
+    any::TypeId,
+    fmt::{self, Write as _},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Adapts an `embedded-hal` 1.0 serial port for `embedded_io::Write`
+///
+/// The adapter blocks until the port accepts each byte.
+pub struct Serial<S>(pub S);
+
+/// A serial port error
+#[derive(Debug)]
+pub struct SerialError<E>(pub E);
+
+impl<E: embedded_hal_nb::serial::Error> embedded_io::Error for SerialError<E> {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        embedded_io::ErrorKind::Other
+    }
+}
+
+impl<S: embedded_hal_nb::serial::ErrorType> embedded_io::ErrorType for Serial<S> {
+    type Error = SerialError<S::Error>;
+}
+
+impl<S: embedded_hal_nb::serial::Write> embedded_io::Write for Serial<S> {
+    fn write(&mut self, bytes: &[u8]) -> Result<usize, Self::Error> {
+        for &byte in bytes {
+            embedded_hal_nb::nb::block!(self.0.write(byte)).map_err(SerialError)?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        embedded_hal_nb::nb::block!(self.0.flush()).map_err(SerialError)
+    }
+}
+
+/// An `embedded_io::Write` implementation, without its error type
+trait Transport: Send {
+    fn write_all(&mut self, bytes: &[u8]) -> fmt::Result;
+    fn flush(&mut self) -> fmt::Result;
+}
+
+impl<W: embedded_io::Write + Send> Transport for W {
+    fn write_all(&mut self, bytes: &[u8]) -> fmt::Result {
+        embedded_io::Write::write_all(self, bytes).map_err(|_| fmt::Error)
+    }
+
+    fn flush(&mut self) -> fmt::Result {
+        embedded_io::Write::flush(self).map_err(|_| fmt::Error)
+    }
+}
+
+struct Logger {
+    writer: &'static mut dyn Transport,
+    /// The writer's type, so that `release()` can return it
+    writer_type: TypeId,
+    filters: Filters,
+}
+
+impl fmt::Write for Logger {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.writer.write_all(string.as_bytes())
+    }
+}
+
+/// Counts the messages that the logger could not write
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// The logger, if initialized
+///
+/// The lock serializes writers.
+static LOGGER: TryLock<Option<Logger>> = TryLock::new(None);
+
+pub(crate) fn enabled(metadata: &::log::Metadata) -> bool {
+    metadata.level() <= ::log::max_level()
+        && matches!(LOGGER.try_lock().as_deref(), Some(Some(logger)) if logger.filters.is_enabled(metadata))
+}
+
+pub(crate) fn log(record: &::log::Record) {
+    if record.level() > ::log::max_level() {
+        return;
+    }
+    let written = LOGGER.try_lock().map(|mut logger| match logger.as_mut() {
+        Some(logger) if logger.filters.is_enabled(record.metadata()) => write!(
+            logger,
+            "[{} {}]: {}\r\n",
+            record.level(),
+            record.target(),
+            record.args()
+        )
+        .is_ok(),
+        _ => true,
+    });
+    if written != Some(true) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn print(args: fmt::Arguments) {
+    let written = LOGGER.try_lock().map(|mut logger| match logger.as_mut() {
+        Some(logger) => logger.write_fmt(args).is_ok(),
+        None => true,
+    });
+    if written != Some(true) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn flush() {
+    // If another context holds the logger, it will finish
+    // writing when we return.
+    if let Some(Some(logger)) = LOGGER.try_lock().as_deref_mut() {
+        // Nothing to do if the writer fails
+        let _ = logger.writer.flush();
+    }
+}
+
+/// Initialize the io logger with a writer
+///
+/// `writer` may be any `embedded_io::Write` implementation. Returns an error if you've already
+/// called `init()`, or if you've already specified a logger through another interface.
+///
+/// If another logger in this crate is initialized, this logger replaces it. See
+/// [Switching loggers](../index.html#switching-loggers) for more information.
+pub fn init<W>(writer: &'static mut W, config: LoggingConfig) -> Result<(), SetLoggerError>
+where
+    W: embedded_io::Write + Send + 'static,
+{
+    critical_section::with(move |_| {
+        let mut logger = LOGGER.try_lock().ok_or(SetLoggerError(()))?;
+        if logger.is_some() {
+            return Err(SetLoggerError(()));
+        }
+
+        dispatch::activate(Backend::Io, config.max_level)?;
+        let writer: &'static mut dyn Transport = writer;
+        early::replay(|bytes| {
+            // Nothing to do if the writer fails
+            let _ = writer.write_all(bytes);
+        });
+        *logger = Some(Logger {
+            writer,
+            writer_type: TypeId::of::<W>(),
+            filters: Filters(config.filters),
+        });
+        Ok(())
+    })
+}
+
+/// Release the io logger's writer
+///
+/// `release()` flushes the writer, then returns the writer that you supplied to `init()`.
+/// After `release()`, the logger discards all messages until you call `init()` again.
+///
+/// `W` is the writer's type. Returns `None` if there is no logger, if the logger's writer is
+/// not a `W`, or if `release()` preempted a context that's logging.
+pub fn release<W>() -> Option<&'static mut W>
+where
+    W: embedded_io::Write + Send + 'static,
+{
+    let mut logger = LOGGER.try_lock()?;
+    if !matches!(logger.as_ref(), Some(logger) if logger.writer_type == TypeId::of::<W>()) {
+        return None;
+    }
+    let Logger { writer, .. } = logger.take()?;
+    // Nothing to do if the writer fails
+    let _ = writer.flush();
+    // Safety: `writer_type` says that the writer is a `W`
+    Some(unsafe { &mut *(writer as *mut dyn Transport as *mut W) })
+}
+
+/// Returns the number of messages that the logger dropped
+///
+/// The logger drops a message if it preempts a writer, or if the writer returns an error.
+/// The count wraps on overflow.
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Write `args` through the io logger, even if another context is writing
+///
+/// Returns `false` if there is no io logger.
+#[cfg(any(feature = "panic-handler", feature = "fault"))]
+pub(crate) fn emergency_write(args: fmt::Arguments) -> bool {
+    // Safety: caller never returns to the context that holds the lock
+    let mut logger = unsafe { LOGGER.steal() };
+    if let Some(logger) = logger.as_mut() {
+        // Nothing to do if the writer fails
+        let _ = logger.write_fmt(args);
+        let _ = logger.writer.flush();
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::{Filter, LoggingConfig};
+    use std::{
+        boxed::Box,
+        io,
+        sync::{Arc, Mutex},
+        vec::Vec,
+    };
+
+    /// A host writer that we can inspect after the logger takes it
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Adapts a `std` writer for `embedded_io::Write`
+    struct Std<W>(W);
+
+    impl<W> embedded_io::ErrorType for Std<W> {
+        type Error = embedded_io::ErrorKind;
+    }
+
+    impl<W: io::Write> embedded_io::Write for Std<W> {
+        fn write(&mut self, bytes: &[u8]) -> Result<usize, Self::Error> {
+            self.0
+                .write(bytes)
+                .map_err(|_| embedded_io::ErrorKind::Other)
+        }
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.0.flush().map_err(|_| embedded_io::ErrorKind::Other)
+        }
+    }
+
+    // The logger is global, so this is the only test that initializes it.
+    #[test]
+    fn log_to_host_writer() {
+        let _lock = crate::tests::lock_logger();
+        static FILTERS: &[Filter] = &[("imxrt_uart_log::io::tests", None)];
+        let output = Shared::default();
+        let writer = Box::leak(Box::new(Std(output.clone())));
+        super::init(
+            writer,
+            LoggingConfig {
+                max_level: ::log::LevelFilter::Info,
+                filters: FILTERS,
+            },
+        )
+        .unwrap();
+        assert!(super::init(Box::leak(Box::new(Std(Vec::new()))), Default::default()).is_err());
+
+        ::log::info!("Hello world! 3 + 2 = {}", 3 + 2);
+        ::log::debug!("Filtered by level");
+        ::log::info!(target: "other", "Filtered by target");
+        ::log::logger().flush();
+
+        assert_eq!(
+            &*output.0.lock().unwrap(),
+            &b"[INFO imxrt_uart_log::io::tests]: Hello world! 3 + 2 = 5\r\n"[..]
+        );
+        assert_eq!(super::dropped(), 0);
+
+        assert!(super::release::<Std<Vec<u8>>>().is_none());
+        let writer = super::release::<Std<Shared>>().unwrap();
+        assert!(super::release::<Std<Shared>>().is_none());
+
+        // Discarded until the next init()
+        ::log::info!("Discarded");
+        super::init(writer, Default::default()).unwrap();
+        ::log::info!(target: "imxrt_uart_log::io::tests", "After init");
+        assert!(output.0.lock().unwrap().ends_with(
+            b"]: Hello world! 3 + 2 = 5\r\n[INFO imxrt_uart_log::io::tests]: After init\r\n"
+        ));
+        assert!(super::release::<Std<Shared>>().is_some());
+    }
+}
@@ -9,17 +9,116 @@
 //! - a [DMA-based](dma/index.html), non-blocking interface. Useful for logging that needs to happen quickly, but does
 //!   not need to be responsive. More complicated to set up than the blocking interface.
 //!
+//! To write the same records to more than one UART, use the [tee](tee/index.html) logger.
+//! To keep recent records in RAM, without a UART, use the [memory](memory/index.html) logger.
+//! To keep them across a warm reset, use a [crash](crash/index.html) log. In an RTIC application,
+//! keep the DMA logger's peripherals in a resource with the [rtic](rtic/index.html) logger. Enable
+//! the `"rtic"` feature to use the `rtic` logger.
+//!
+//! The loggers accept `imxrt-hal` 0.4 UARTs and DMA channels. If you've upgraded to `imxrt-hal`
+//! 0.5, or you're not using an imxrt106x, enable the `"lpuart"` feature, and use the [lpuart](lpuart/index.html) loggers. To log
+//! through any `embedded-io` writer, or `embedded-hal` 1.0 serial port, enable the `"io"`
+//! feature, and use the [io](io/index.html) logger. To send records from an async task, enable
+//! the `"async"` feature, and use the [asynch](asynch/index.html) logger. To send
+//! [`defmt`](https://defmt.ferrous-systems.com) frames instead of formatted text, enable the
+//! `"defmt"` feature, and use the [defmt](defmt/index.html) global logger.
+//!
 //! The module-level documentation provides examples and recommended use-cases. To see some comparisons between the two,
 //! see [Performance](#performance).
 //!
+//! # Switching loggers
+//!
+//! The crate registers a single logger with the `log` crate, and that logger dispatches records
+//! to the most recently initialized implementation. You may initialize the blocking logger at
+//! startup, then call [`dma::init()`](dma/fn.init.html) once DMA is ready; the DMA logger receives all
+//! subsequent records. The blocking logger keeps its UART until you
+//! [`release()`](blocking/fn.release.html) it.
+//!
+//! To move an implementation to a different UART, `release()` it, and call its `init()` again.
+//! For example, after changing the pin multiplexing,
+//!
+//! ```no_run
+//! # fn uart6_tx() -> imxrt_hal::uart::Tx<imxrt_hal::iomuxc::consts::U6> { unimplemented!() }
+//! let uart2_tx = imxrt_uart_log::blocking::release().unwrap();
+//! imxrt_uart_log::blocking::init(uart6_tx(), Default::default()).unwrap();
+//! ```
+//!
+//! Messages logged between `release()` and `init()` are discarded.
+//!
+//! # Rate limiting and duplicate suppression
+//!
+//! To keep a noisy target, or call site, from starving all other records, use the
+//! [rate limiter](ratelimit/index.html). It works with every logger.
+//!
+//! To collapse runs of identical records into one record, and a "last message repeated N
+//! times" summary, enable [duplicate suppression](dedup/index.html).
+//!
+//! # Printing
+//!
+//! To write text without a level or target, use [`uart_print!`](macro.uart_print.html) and
+//! [`uart_println!`](macro.uart_println.html). The macros write through the active logger,
+//! and they bypass the `log` crate, and the logger's filters.
+//!
+//! # Early logging
+//!
+//! Messages logged before the first `init()` are discarded, unless you call
+//! [`early::init()`](early/index.html). The early logger captures records in a static
+//! buffer, and the first `init()` writes them to the UART.
+//!
 //! # i.MX RT Compatibility
 //!
-//! This crate supports all of the same i.MX RT variants as the `imxrt-hal` crate.
-//! To see the supported i.MX RT variants, check the [HAL's feature support](https://github.com/imxrt-rs/imxrt-rs#hal) list.
+//! Select your variant with one of this crate's features:
+//!
+//! | Feature         | LPUART instances | DMA channels | Loggers                     |
+//! | --------------- | ---------------- | ------------ | --------------------------- |
+//! | `"imxrt1011"`   | 4                | 16           | `lpuart`, `asynch`, generic |
+//! | `"imxrt1021"`   | 8                | 32           | `lpuart`, `asynch`, generic |
+//! | `"imxrt1052"`   | 8                | 32           | generic                     |
+//! | `"imxrt1061"`   | 8                | 32           | all                         |
+//! | `"imxrt1062"`   | 8                | 32           | all                         |
+//! | `"imxrt1064"`   | 8                | 32           | all                         |
+//! | `"imxrt1176"`   | 12               | 32           | `lpuart`, `asynch`, generic |
 //!
-//! **Note**: As of this writing, the HAL only supports one i.MX RT variant, the `"imxrt1062"`.
-//! For convenience, the `"imxrt1062"` feature is enabled **by default**. This may change in the
-//! future.
+//! The blocking, DMA, tee, `rtic`, and `defmt` loggers use `imxrt-hal` 0.4, which only supports
+//! the imxrt106x variants. The `lpuart` and `asynch` loggers use `imxrt-hal` 0.5, which doesn't
+//! support the imxrt1052. The generic loggers, `io`, `memory`, `crash`, and `early`, don't use a
+//! HAL, so they support every variant. The `"imxrt1176"` feature selects the Cortex-M7 core.
+//!
+//! For convenience, the `"imxrt1062"` feature is enabled **by default**. To select another
+//! variant, disable the default features. This may change in the future.
+//!
+//! # Critical sections
+//!
+//! The loggers use the [`critical-section`](https://crates.io/crates/critical-section) crate
+//! to serialize access to their state. Your program must provide a critical section
+//! implementation. On a single-core i.MX RT, enable the `cortex-m` crate's
+//! `"critical-section-single-core"` feature:
+//!
+//! ```toml
+//! [dependencies]
+//! cortex-m = { version = "0.7", features = ["critical-section-single-core"] }
+//! ```
+//!
+//! On a host, like when you test code that logs through the [io](io/index.html) logger, enable
+//! the `critical-section` crate's `"std"` feature.
+//!
+//! # Panic handler
+//!
+//! Enable the `"panic-handler"` feature to use this crate's panic handler. The handler disables
+//! interrupts, and writes the panic message and location through the logger that you initialized.
+//! Then, it loops forever.
+//!
+//! - The blocking logger writes the message, even if the panic preempted another writer.
+//! - The DMA logger [switches to blocking writes](dma/fn.switch_to_blocking.html), which sends all
+//!   enqueued messages. Then, it writes the panic message. If the panic preempted the DMA logger,
+//!   the handler stops the DMA transfer, and writes the panic message with the UART's registers.
+//!   The enqueued messages are lost.
+//! - The memory logger writes the message into its buffer.
+//!
+//! If there is no logger, the handler writes nothing. Don't use another panic handler, like
+//! `panic-halt`, when you enable this feature.
+//!
+//! For a similar report from a `HardFault` handler, see the [`fault`](fault/index.html) module.
 //!
 //! # Performance
 //!
@@ -54,9 +153,91 @@
 
 #![no_std]
 
+#[cfg(not(any(
+    feature = "imxrt1011",
+    feature = "imxrt1021",
+    feature = "imxrt1052",
+    feature = "imxrt1061",
+    feature = "imxrt1062",
+    feature = "imxrt1064",
+    feature = "imxrt1176",
+)))]
+compile_error!("Select an i.MX RT variant with one of this crate's features, like \"imxrt1062\"");
+
+#[cfg(any(
+    all(feature = "imxrt1011", feature = "imxrt1021"),
+    all(feature = "imxrt1011", feature = "imxrt1052"),
+    all(feature = "imxrt1011", feature = "imxrt1061"),
+    all(feature = "imxrt1011", feature = "imxrt1062"),
+    all(feature = "imxrt1011", feature = "imxrt1064"),
+    all(feature = "imxrt1011", feature = "imxrt1176"),
+    all(feature = "imxrt1021", feature = "imxrt1052"),
+    all(feature = "imxrt1021", feature = "imxrt1061"),
+    all(feature = "imxrt1021", feature = "imxrt1062"),
+    all(feature = "imxrt1021", feature = "imxrt1064"),
+    all(feature = "imxrt1021", feature = "imxrt1176"),
+    all(feature = "imxrt1052", feature = "imxrt1061"),
+    all(feature = "imxrt1052", feature = "imxrt1062"),
+    all(feature = "imxrt1052", feature = "imxrt1064"),
+    all(feature = "imxrt1052", feature = "imxrt1176"),
+    all(feature = "imxrt1061", feature = "imxrt1062"),
+    all(feature = "imxrt1061", feature = "imxrt1064"),
+    all(feature = "imxrt1061", feature = "imxrt1176"),
+    all(feature = "imxrt1062", feature = "imxrt1064"),
+    all(feature = "imxrt1062", feature = "imxrt1176"),
+    all(feature = "imxrt1064", feature = "imxrt1176"),
+))]
+compile_error!(
+    "Select only one i.MX RT variant. Use default-features = false to select a variant other than \"imxrt1062\""
+);
+
+#[cfg(all(feature = "imxrt1052", feature = "lpuart"))]
+compile_error!(
+    "imxrt-hal 0.5 doesn't support the imxrt1052, so it can't use the \"lpuart\" feature"
+);
+
+#[cfg(all(any(feature = "rtic", feature = "defmt"), not(feature = "imxrt-hal")))]
+compile_error!("The \"rtic\" and \"defmt\" features need an imxrt106x variant");
+
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "imxrt-hal")]
 pub mod blocking;
+pub mod crash;
+pub mod dedup;
+#[cfg(all(feature = "defmt", feature = "imxrt-hal"))]
+pub mod defmt;
+mod dispatch;
+#[cfg(feature = "imxrt-hal")]
 pub mod dma;
+pub mod early;
+#[cfg(feature = "fault")]
+pub mod fault;
 mod filters;
+mod fnv;
+pub mod hexdump;
+#[cfg(feature = "io")]
+pub mod io;
+mod lock;
+#[cfg(feature = "lpuart")]
+pub mod lpuart;
+pub mod memory;
+mod name;
+mod once;
+#[cfg(feature = "panic-handler")]
+mod panic;
+#[cfg(any(feature = "imxrt-hal", feature = "lpuart"))]
+mod poll;
+pub mod ratelimit;
+mod ring;
+#[cfg(all(feature = "rtic", feature = "imxrt-hal"))]
+pub mod rtic;
+#[cfg(all(feature = "rtic", feature = "imxrt-hal"))]
+mod spsc;
+#[cfg(feature = "imxrt-hal")]
+pub mod tee;
+#[cfg(any(feature = "imxrt-hal", feature = "lpuart"))]
+mod timeout;
 
 pub use filters::Filter;
 use filters::Filters;
@@ -94,12 +275,12 @@ pub struct LoggingConfig {
     /// override this if they'd like to bypass the statically-assigned
     /// max level
     pub max_level: ::log::LevelFilter,
-    /// A list of filtered targets to log.
+    /// A list of filtered targets to log, or to exclude.
     ///
     /// If set to an empty slice (default), the logger performs no
     /// filtering. Otherwise, we filter the specified targets by
-    /// the accompanying log level. See [`Filter`](type.Filter.html) for
-    /// more information.
+    /// the accompanying log level. A `LevelFilter::Off` level excludes
+    /// a target. See [`Filter`](type.Filter.html) for more information.
     pub filters: &'static [Filter],
 }
 
@@ -114,9 +295,10 @@ impl Default for LoggingConfig {
 
 /// An error that indicates the logger is already set
 ///
-/// The error could propagate from one of the `init()` functions.
-/// Or, it could propagate if the underlying logger was set through another logging
-/// interface.
+/// The error could propagate from one of the `init()` functions, if that logger is
+/// already initialized. Or, it could propagate if the underlying logger was set through
+/// another logging interface. It also propagates if `init()` preempts another `init()`
+/// call, like when an interrupt handler initializes the logger.
 #[derive(Debug)]
 pub struct SetLoggerError(());
 
@@ -125,3 +307,124 @@ impl From<::log::SetLoggerError> for SetLoggerError {
         SetLoggerError(())
     }
 }
+
+/// Write `args` through the active logger
+///
+/// Use [`uart_print!`](macro.uart_print.html) and [`uart_println!`](macro.uart_println.html)
+/// instead of calling this directly.
+#[doc(hidden)]
+pub fn _print(args: core::fmt::Arguments) {
+    dispatch::print(args);
+}
+
+/// Log a byte buffer as a hex dump, one record per line
+///
+/// `log_hex!` accepts an optional target, an optional width, a `log::Level`, and a `&[u8]`.
+/// Each record, including its `[LEVEL target]: ` prefix, fits in the width. The default
+/// width is [`hexdump::DEFAULT_WIDTH`](hexdump/constant.DEFAULT_WIDTH.html). See the
+/// [`hexdump`](hexdump/index.html) module for the format.
+///
+/// ```
+/// # let frame = [0u8; 20];
+/// imxrt_uart_log::log_hex!(log::Level::Debug, &frame);
+/// imxrt_uart_log::log_hex!(target: "spi", log::Level::Trace, &frame[..4]);
+/// imxrt_uart_log::log_hex!(width: 120, log::Level::Debug, &frame);
+/// ```
+#[macro_export]
+macro_rules! log_hex {
+    (target: $target:expr, width: $width:expr, $level:expr, $bytes:expr) => {{
+        let target: &str = $target;
+        let level: $crate::_log::Level = $level;
+        let width = $crate::hexdump::_record_width(level, target, $width);
+        for line in $crate::hexdump::HexDump::new($bytes).with_width(width).lines() {
+            $crate::_log::log!(target: target, level, "{}", line);
+        }
+    }};
+    (target: $target:expr, $level:expr, $bytes:expr) => {
+        $crate::log_hex!(target: $target, width: $crate::hexdump::DEFAULT_WIDTH, $level, $bytes)
+    };
+    (width: $width:expr, $level:expr, $bytes:expr) => {
+        $crate::log_hex!(target: ::core::module_path!(), width: $width, $level, $bytes)
+    };
+    ($level:expr, $bytes:expr) => {
+        $crate::log_hex!(target: ::core::module_path!(), $level, $bytes)
+    };
+}
+
+#[doc(hidden)]
+pub use ::log as _log;
+
+/// Write formatted text through the active logger
+///
+/// `uart_print!` accepts the same arguments as `format_args!`. The active logger writes the
+/// text as-is: there's no level or target, no line terminator, and no filtering. Use it to
+/// stream data, like CSV rows or progress bars, to the same UART as your log records.
+///
+/// If there's no logger, `uart_print!` writes nothing.
+///
+/// ```no_run
+/// # let (x, y) = (1, 2);
+/// imxrt_uart_log::uart_print!("{},{}", x, y);
+/// ```
+#[macro_export]
+macro_rules! uart_print {
+    ($($arg:tt)*) => {
+        $crate::_print(format_args!($($arg)*))
+    };
+}
+
+/// Write formatted text, and a line terminator, through the active logger
+///
+/// `uart_println!` behaves like [`uart_print!`](macro.uart_print.html), then writes
+/// `"\r\n"`, the same line terminator as a log record.
+///
+/// ```no_run
+/// # let (x, y) = (1, 2);
+/// imxrt_uart_log::uart_println!("{},{}", x, y);
+/// imxrt_uart_log::uart_println!();
+/// ```
+#[macro_export]
+macro_rules! uart_println {
+    () => {
+        $crate::_print(format_args!("\r\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::_print(format_args!("{}\r\n", format_args!($($arg)*)))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{
+        boxed::Box,
+        sync::{Mutex, MutexGuard},
+        vec::Vec,
+    };
+
+    /// Serializes tests that initialize, or log through, the global logger
+    ///
+    /// The `log` crate has one logger for the test process, and tests run in parallel.
+    pub(crate) fn lock_logger() -> MutexGuard<'static, ()> {
+        static LOCK: Mutex<()> = Mutex::new(());
+        LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[test]
+    fn print_macros() {
+        let _lock = lock_logger();
+        crate::memory::init(Box::leak(Box::new([0; 64])), Default::default()).unwrap();
+
+        crate::uart_print!("{},", 1);
+        crate::uart_println!("{}", 2);
+        crate::uart_println!();
+        crate::uart_print!("no terminator");
+
+        let contents = crate::memory::contents(|first, second| [first, second].concat());
+        crate::memory::release().unwrap();
+        assert_eq!(contents.as_deref(), Some(&b"1,2\r\n\r\nno terminator"[..]));
+        // There's no logger to write through
+        crate::uart_println!("discarded");
+        assert!(crate::memory::contents(|first, _| Vec::from(first)).is_none());
+    }
+}
@@ -0,0 +1,96 @@
+//! A lock that never blocks
+
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A lock that does not disable interrupts, and does not spin
+///
+/// `try_lock()` returns `None` if the lock is already held. On a single
+/// core, the only way to observe a held lock is to preempt its holder.
+/// The holder cannot make progress until the preempting context returns,
+/// so the preempting context must not wait for the lock.
+pub struct TryLock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// Safety: access to `value` is serialized by `locked`.
+unsafe impl<T: Send> Sync for TryLock<T> {}
+
+impl<T> TryLock<T> {
+    pub const fn new(value: T) -> Self {
+        TryLock {
+            locked: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Acquire the lock, or return `None` if the lock is held
+    pub fn try_lock(&self) -> Option<Guard<'_, T>> {
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            Some(Guard { lock: self })
+        } else {
+            None
+        }
+    }
+
+    /// Acquire the lock, even if another context holds the lock
+    ///
+    /// # Safety
+    ///
+    /// The caller must never return to the context that holds the lock.
+    /// Use this in panic and fault handlers that do not return.
+    #[cfg(any(feature = "panic-handler", feature = "fault"))]
+    pub unsafe fn steal(&self) -> Guard<'_, T> {
+        self.locked.swap(true, Ordering::Acquire);
+        Guard { lock: self }
+    }
+}
+
+/// Provides access to the locked value, and releases the lock when dropped
+pub struct Guard<'a, T> {
+    lock: &'a TryLock<T>,
+}
+
+impl<T> Deref for Guard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // Safety: we hold the lock
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for Guard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: we hold the lock
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for Guard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TryLock;
+
+    #[test]
+    fn held_lock_is_unavailable() {
+        let lock = TryLock::new(5u32);
+        let mut guard = lock.try_lock().unwrap();
+        *guard += 1;
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+        assert_eq!(*lock.try_lock().unwrap(), 6);
+    }
+}
@@ -0,0 +1,515 @@
+//! Logging with the `imxrt-hal` 0.5 LPUART and DMA drivers
+//!
+//! The other loggers in this crate use `imxrt-hal` 0.4 UART and DMA types. This module accepts
+//! an `imxrt_hal::lpuart::Lpuart`, and an `imxrt_hal::dma::channel::Channel`, from `imxrt-hal`
+//! 0.5. Enable the `"lpuart"` feature to use this module.
+//!
+//! - [`init_blocking`](fn.init_blocking.html) writes with blocking LPUART writes. It respects
+//!   the [timeout](fn.set_poll_timeout.html).
+//! - [`init_dma`](fn.init_dma.html) writes through a circular buffer with DMA transfers. Call
+//!   [`poll`](fn.poll.html) from your DMA channel's interrupt handler, or throughout an event
+//!   loop.
+//!
+//! Both loggers take a `&'static mut` LPUART, which you can create with
+//! `cortex_m::singleton!`. Configure the LPUART's baud rate, parity, and FIFOs before you
+//! supply it to the logger. The logger keeps the LPUART, and its DMA channel, until you call
+//! [`release`](fn.release.html).
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use imxrt_hal_05 as imxrt_hal;
+//! use imxrt_hal::{dma::channel::Channel, lpuart::Lpuart};
+//! # type Pins = ();
+//! # fn lpuart2() -> Lpuart<Pins, 2> { unimplemented!() }
+//! # fn dma_channel() -> Channel { unimplemented!() }
+//!
+//! // Assume that DMA7_DMA23 is registered in the vector table
+//! fn DMA7_DMA23() {
+//!     imxrt_uart_log::lpuart::poll();
+//! }
+//!
+//! let uart = cortex_m::singleton!(: Lpuart<Pins, 2> = lpuart2()).unwrap();
+//! let buffer = cortex_m::singleton!(: [u8; 2048] = [0; 2048]).unwrap();
+//!
+//! let mut channel = dma_channel();
+//! channel.set_interrupt_on_completion(true);
+//! imxrt_uart_log::lpuart::init_dma(uart, channel, buffer, Default::default()).unwrap();
+//!
+//! log::info!("Transferred with DMA");
+//! ```
+//!
+//! # Implementation
+//!
+//! The DMA logger copies each record into the buffer that you supply, then sends the enqueued
+//! records with the LPUART's `dma_write()` transfers. Unlike the `imxrt-hal` 0.4 DMA logger,
+//! the buffer has no alignment requirements. If a record doesn't fit in the buffer's free
+//! space, the logger **drops** the whole record. Use [`dropped`](fn.dropped.html) to learn how
+//! many records were dropped.
+//!
+//! Both loggers write in an interrupt free critical section. In a panic or fault handler, the
+//! DMA logger waits for its active transfer, then writes all enqueued data, and the message,
+//! with blocking writes.
+
+use crate::{
+    dispatch::{self, Backend},
+    early,
+    lock::TryLock,
+    ring::Ring,
+    timeout, Filters, LoggingConfig, SetLoggerError,
+};
+use core::{
+    any::TypeId,
+    fmt::{self, Write},
+    slice,
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+use imxrt_hal_05::{
+    dma::{
+        channel::{self, Channel, Configuration},
+        peripheral::Destination,
+    },
+    lpuart::{Lpuart, Status},
+};
+
+pub use crate::{poll::Poll, timeout::set_poll_timeout};
+
+/// An LPUART that the logger writes to
+///
+/// `Transmit` is implemented for every `imxrt_hal::lpuart::Lpuart` from `imxrt-hal` 0.5.
+pub trait Transmit: Destination<u8> + Send + 'static {
+    /// Write `byte` into the transmit FIFO
+    ///
+    /// Returns `false` if the FIFO is full.
+    fn try_write(&mut self, byte: u8) -> bool;
+    /// Returns `true` if the LPUART finished transmitting all data
+    fn is_transmit_complete(&self) -> bool;
+}
+
+impl<P: Send + 'static, const N: u8> Transmit for Lpuart<P, N> {
+    fn try_write(&mut self, byte: u8) -> bool {
+        Lpuart::try_write(self, byte)
+    }
+
+    fn is_transmit_complete(&self) -> bool {
+        self.status().contains(Status::TRANSMIT_COMPLETE)
+    }
+}
+
+/// Start a DMA transfer of `bytes` from `channel` to `uart`
+///
+/// The transfer is active until the channel is complete, or has an error. Unlike
+/// `dma_write()`, there's no future to keep alive, so the transfer may outlive this call.
+fn start_transfer(channel: &mut Channel, uart: &mut dyn Transmit, bytes: &'static [u8]) {
+    channel.disable();
+    channel.set_disable_on_completion(true);
+    channel.set_channel_configuration(Configuration::enable(uart.destination_signal()));
+    // Safety: the LPUART's destination address is valid. The bytes are static, and the
+    // transfer iterations don't exceed them.
+    unsafe {
+        channel::set_source_linear_buffer(channel, bytes);
+        channel::set_destination_hardware(channel, uart.destination_address());
+        channel.set_minor_loop_bytes(1);
+        channel.set_transfer_iterations(bytes.len() as u16);
+    }
+    uart.enable_destination();
+    atomic::fence(Ordering::SeqCst);
+    // Safety: the logger owns the channel, and the bytes outlive the transfer.
+    unsafe { channel.enable() };
+}
+
+/// Writes to an LPUART, respecting the poll timeout
+struct Writer<'a>(&'a mut dyn Transmit);
+
+impl Writer<'_> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> fmt::Result {
+        for &byte in bytes {
+            if !timeout::poll(|| self.0.try_write(byte)) {
+                // Try to end the partial line, so the next message starts on its own line
+                let _ = b"\r\n"
+                    .iter()
+                    .all(|&byte| timeout::poll(|| self.0.try_write(byte)));
+                return Err(fmt::Error);
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> fmt::Result {
+        if timeout::poll(|| self.0.is_transmit_complete()) {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+impl fmt::Write for Writer<'_> {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.write_bytes(string.as_bytes())
+    }
+}
+
+/// The maximum number of bytes in one DMA transfer
+///
+/// The transfer iteration count is a 15 bit field, and neither `dma_write()` nor
+/// `start_transfer()` checks the length.
+pub(crate) const MAX_TRANSFER: usize = 0x7FFF;
+
+/// Number of records dropped by the DMA logger
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// DMA transfer state
+struct Dma {
+    channel: Channel,
+    queue: Ring<&'static mut [u8]>,
+    /// The number of bytes, at the front of the queue, that the
+    /// active transfer is sending. Zero if there is no active transfer.
+    active: usize,
+}
+
+impl Dma {
+    fn new(channel: Channel, buffer: &'static mut [u8]) -> Self {
+        channel.disable();
+        Dma {
+            channel,
+            queue: Ring::new(buffer),
+            active: 0,
+        }
+    }
+
+    /// Enqueue a record, and start a transfer if there isn't one
+    fn write_fmt(&mut self, uart: &mut dyn Transmit, args: fmt::Arguments) {
+        if !self.queue.push_fmt(args) {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+        self.poll(uart);
+    }
+
+    /// Enqueue `bytes`, and start a transfer if there isn't one
+    fn write_bytes(&mut self, uart: &mut dyn Transmit, bytes: &[u8]) {
+        // Data that doesn't fit is dropped
+        self.queue.push(bytes);
+        self.poll(uart);
+    }
+
+    /// Finish a completed transfer, and start the next transfer
+    fn poll(&mut self, uart: &mut dyn Transmit) -> Poll {
+        if self.active > 0 {
+            if !self.channel.is_complete() && !self.channel.is_error() {
+                return Poll::Active;
+            }
+            self.finish(uart);
+        }
+        self.start(uart)
+    }
+
+    /// Release the bytes sent by the active transfer
+    ///
+    /// If the transfer failed, the bytes are dropped.
+    fn finish(&mut self, uart: &mut dyn Transmit) {
+        self.channel.disable();
+        self.channel.clear_complete();
+        self.channel.clear_error();
+        self.channel.clear_interrupt();
+        uart.disable_destination();
+        self.queue.consume(self.active);
+        self.active = 0;
+    }
+
+    /// Start a transfer of the enqueued bytes that are contiguous in the buffer
+    fn start(&mut self, uart: &mut dyn Transmit) -> Poll {
+        let (first, _) = self.queue.as_slices();
+        if first.is_empty() {
+            return Poll::Idle;
+        }
+        let len = first.len().min(MAX_TRANSFER);
+        // Safety: the bytes are in a static buffer. The queue only appends into
+        // the free space after the enqueued bytes, so it won't modify these bytes
+        // until we consume them.
+        let bytes = unsafe { slice::from_raw_parts(first.as_ptr(), len) };
+        start_transfer(&mut self.channel, uart, bytes);
+        self.active = len;
+        Poll::Active
+    }
+
+    /// Wait for the active transfer to finish
+    ///
+    /// If the transfer doesn't finish before the timeout, stop it, and drop its bytes.
+    fn wait(&mut self, uart: &mut dyn Transmit) {
+        if self.active > 0 {
+            // Nothing to do if we time out; finish() stops the transfer
+            let _ = timeout::poll(|| self.channel.is_complete() || self.channel.is_error());
+            self.finish(uart);
+        }
+    }
+
+    /// Wait for the active transfer, then write all enqueued bytes with blocking writes
+    #[cfg(any(feature = "panic-handler", feature = "fault"))]
+    fn drain_blocking(&mut self, uart: &mut dyn Transmit) {
+        self.wait(uart);
+        let (first, second) = self.queue.as_slices();
+        let mut writer = Writer(uart);
+        // Nothing to do if we time out
+        let _ = writer.write_bytes(first);
+        let _ = writer.write_bytes(second);
+        self.queue.clear();
+    }
+}
+
+struct Logger {
+    uart: &'static mut dyn Transmit,
+    /// The type of `uart`
+    uart_type: TypeId,
+    dma: Option<Dma>,
+    filters: Filters,
+}
+
+impl Logger {
+    fn write(&mut self, args: fmt::Arguments) {
+        match &mut self.dma {
+            Some(dma) => dma.write_fmt(self.uart, args),
+            // Nothing to do if we time out
+            None => {
+                let _ = Writer(self.uart).write_fmt(args);
+            }
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        match &mut self.dma {
+            Some(dma) => dma.write_bytes(self.uart, bytes),
+            // Nothing to do if we time out
+            None => {
+                let _ = Writer(self.uart).write_bytes(bytes);
+            }
+        }
+    }
+}
+
+/// The logger, if initialized
+static LOGGER: TryLock<Option<Logger>> = TryLock::new(None);
+
+/// Run `f` with the logger in a critical section
+///
+/// Returns `None` if another context holds the logger.
+fn with_logger<R>(f: impl FnOnce(&mut Option<Logger>) -> R) -> Option<R> {
+    critical_section::with(|_| LOGGER.try_lock().map(|mut logger| f(&mut logger)))
+}
+
+pub(crate) fn enabled(metadata: &::log::Metadata) -> bool {
+    metadata.level() <= ::log::max_level()
+        && with_logger(
+            |logger| matches!(logger.as_ref(), Some(logger) if logger.filters.is_enabled(metadata)),
+        )
+        .unwrap_or(false)
+}
+
+pub(crate) fn log(record: &::log::Record) {
+    if record.level() > ::log::max_level() {
+        return;
+    }
+    with_logger(|logger| {
+        if let Some(logger) = logger {
+            if logger.filters.is_enabled(record.metadata()) {
+                logger.write(format_args!(
+                    "[{} {}]: {}\r\n",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ));
+            }
+        }
+    });
+}
+
+pub(crate) fn print(args: fmt::Arguments) {
+    with_logger(|logger| {
+        if let Some(logger) = logger {
+            logger.write(args);
+        }
+    });
+}
+
+pub(crate) fn flush() {
+    with_logger(|logger| {
+        if let Some(Logger {
+            uart, dma: None, ..
+        }) = logger
+        {
+            // Nothing to do if we time out
+            let _ = Writer(*uart).flush();
+        }
+    });
+}
+
+fn init<T: Transmit>(
+    uart: &'static mut T,
+    dma: Option<Dma>,
+    config: LoggingConfig,
+) -> Result<(), SetLoggerError> {
+    critical_section::with(move |_| {
+        let mut current = LOGGER.try_lock().ok_or(SetLoggerError(()))?;
+        if current.is_some() {
+            return Err(SetLoggerError(()));
+        }
+
+        dispatch::activate(Backend::Lpuart, config.max_level)?;
+        let logger = current.get_or_insert(Logger {
+            uart,
+            uart_type: TypeId::of::<T>(),
+            dma,
+            filters: Filters(config.filters),
+        });
+        early::replay(|bytes| logger.write_bytes(bytes));
+        Ok(())
+    })
+}
+
+/// Initialize a logger that blocks while it writes to `uart`
+///
+/// Returns an error if you've already initialized an `lpuart` logger, or if you've already
+/// specified a logger through another interface.
+///
+/// If another logger in this crate is initialized, this logger replaces it. See
+/// [Switching loggers](../index.html#switching-loggers) for more information.
+pub fn init_blocking<T: Transmit>(
+    uart: &'static mut T,
+    config: LoggingConfig,
+) -> Result<(), SetLoggerError> {
+    init(uart, None, config)
+}
+
+/// Initialize a logger that writes to `uart` with DMA transfers
+///
+/// The logger enqueues records in `buffer`, and sends them with `channel`. If you'd like
+/// to call [`poll`](fn.poll.html) from the channel's interrupt handler, enable the channel's
+/// interrupt on completion before you call `init_dma()`.
+///
+/// Returns an error if you've already initialized an `lpuart` logger, or if you've already
+/// specified a logger through another interface.
+///
+/// If another logger in this crate is initialized, this logger replaces it. See
+/// [Switching loggers](../index.html#switching-loggers) for more information.
+pub fn init_dma<T: Transmit>(
+    uart: &'static mut T,
+    channel: Channel,
+    buffer: &'static mut [u8],
+    config: LoggingConfig,
+) -> Result<(), SetLoggerError> {
+    init(uart, Some(Dma::new(channel, buffer)), config)
+}
+
+/// Release the `lpuart` logger's LPUART, and its DMA channel and buffer
+///
+/// `release()` waits for all enqueued data to transfer, then returns the LPUART that you
+/// supplied to `init_blocking()` or `init_dma()`. If you called `init_dma()`, it also returns
+/// the DMA channel, and the buffer. If a transfer doesn't finish within the
+/// [poll timeout](fn.set_poll_timeout.html), `release()` stops it, and drops its data. After `release()`, the logger discards all messages until
+/// you call `init_blocking()` or `init_dma()` again. `poll()` returns `Idle` until you
+/// initialize a DMA logger, so you may keep calling `poll()` in your DMA channel's interrupt
+/// handler.
+///
+/// Returns `None` if there is no logger, or if `T` isn't the logger's LPUART type. Also
+/// returns `None` if `release()` preempted a context that's logging.
+///
+/// ```no_run
+/// # use imxrt_hal_05 as imxrt_hal;
+/// use imxrt_hal::lpuart::Lpuart;
+/// # type Pins = ();
+///
+/// let (uart, dma) = imxrt_uart_log::lpuart::release::<Lpuart<Pins, 2>>().unwrap();
+/// if let Some((channel, buffer)) = dma {
+///     // Use the DMA channel and buffer...
+/// }
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn release<T: Transmit>() -> Option<(&'static mut T, Option<(Channel, &'static mut [u8])>)> {
+    // Wait for enqueued data outside of a critical section, so
+    // we don't block interrupts for the entire transfer...
+    let _ = timeout::poll(|| {
+        with_logger(|logger| match logger {
+            Some(Logger {
+                uart,
+                uart_type,
+                dma: Some(dma),
+                ..
+            }) if *uart_type == TypeId::of::<T>() => dma.poll(*uart),
+            _ => Poll::Idle,
+        }) != Some(Poll::Active)
+    });
+    // ...then wait for anything that was logged in the meantime.
+    let logger = with_logger(|logger| {
+        if matches!(logger, Some(Logger { uart_type, .. }) if *uart_type == TypeId::of::<T>()) {
+            logger.take()
+        } else {
+            None
+        }
+    })
+    .flatten()?;
+    let Logger { uart, dma, .. } = logger;
+    let dma = dma.map(|mut dma| {
+        // Each wait is bounded by the timeout, and consumes enqueued bytes
+        while Poll::Active == dma.poll(uart) {
+            dma.wait(uart);
+        }
+        (dma.channel, dma.queue.into_inner())
+    });
+    if dma.is_none() {
+        // Nothing to do if we time out
+        let _ = Writer(uart).flush();
+    }
+    // Safety: `uart_type` says that the LPUART is a `T`
+    let uart = unsafe { &mut *(uart as *mut dyn Transmit as *mut T) };
+    Some((uart, dma))
+}
+
+/// Returns the number of records that the DMA logger dropped
+///
+/// The logger drops a record if it doesn't fit in the buffer's free space.
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Drives the DMA logger
+///
+/// `poll()` finishes a completed transfer, and starts the next transfer. Call `poll()` from
+/// the DMA channel's interrupt handler, or throughout an event loop. See
+/// [`Poll`](enum.Poll.html) for the meaning of the return.
+///
+/// Returns `Poll::Idle` if there is no DMA logger, or if `poll()` preempted a context that's
+/// logging.
+pub fn poll() -> Poll {
+    with_logger(|logger| match logger {
+        Some(Logger {
+            uart,
+            dma: Some(dma),
+            ..
+        }) => dma.poll(*uart),
+        _ => Poll::Idle,
+    })
+    .unwrap_or(Poll::Idle)
+}
+
+/// Write `args` with blocking writes, even if another context is writing
+///
+/// Returns `false` if there is no `lpuart` logger.
+#[cfg(any(feature = "panic-handler", feature = "fault"))]
+pub(crate) fn emergency_write(args: fmt::Arguments) -> bool {
+    critical_section::with(|_| {
+        // Safety: caller never returns to the context that holds the lock
+        let mut logger = unsafe { LOGGER.steal() };
+        if let Some(logger) = logger.as_mut() {
+            if let Some(dma) = &mut logger.dma {
+                dma.drain_blocking(logger.uart);
+            }
+            let mut writer = Writer(logger.uart);
+            // Nothing to do if we time out
+            let _ = writer.write_fmt(args);
+            let _ = writer.flush();
+            true
+        } else {
+            false
+        }
+    })
+}
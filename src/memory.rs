@@ -0,0 +1,216 @@
+//! Logging into a RAM buffer
+//!
+//! The memory logger writes formatted records into a buffer that you supply. It does not
+//! use a UART. When the buffer is full, the logger overwrites the oldest records, so the
+//! buffer keeps your most recent logs. Read the logs back later, and send them through a
+//! UART console, or inspect the buffer with a debugger.
+//!
+//! ```no_run
+//! use imxrt_uart_log::memory;
+//!
+//! let buffer = cortex_m::singleton!(: [u8; 4096] = [0; 4096]).unwrap();
+//! memory::init(buffer, Default::default()).unwrap();
+//!
+//! log::info!("Stored in RAM");
+//!
+//! // Later, when a user connects a serial cable...
+//! let mut chunk = [0; 64];
+//! loop {
+//!     let len = memory::read(&mut chunk);
+//!     if len == 0 {
+//!         break;
+//!     }
+//!     // Send &chunk[..len] through the UART console...
+//! }
+//! ```
+//!
+//! The memory logger writes in an interrupt free critical section. It discards the oldest
+//! record if the logger overwrote part of it, so you only read complete records.
+//!
+//! If you enable the `"panic-handler"` feature, the panic handler writes the panic message
+//! into the buffer.
+
+use crate::{
+    crash::Storage,
+    dispatch::{self, Backend},
+    early,
+    lock::TryLock,
+    ring::Records,
+    Filters, LoggingConfig, SetLoggerError,
+};
+use core::fmt;
+
+struct Memory {
+    records: Records<Storage<&'static mut [u8]>>,
+    filters: Filters,
+}
+
+impl Memory {
+    /// Save the state of a crash log
+    fn save(&mut self) {
+        Storage::save(&mut self.records);
+    }
+
+    /// Write `args`, and save the state of a crash log
+    fn write(&mut self, args: fmt::Arguments) {
+        Storage::write_fmt(&mut self.records, args);
+    }
+}
+
+/// The logger, if initialized
+static MEMORY: TryLock<Option<Memory>> = TryLock::new(None);
+
+/// Run `f` with the logger in a critical section
+///
+/// Returns `None` if another context holds the logger.
+fn with_memory<R>(f: impl FnOnce(&mut Option<Memory>) -> R) -> Option<R> {
+    critical_section::with(|_| MEMORY.try_lock().map(|mut memory| f(&mut memory)))
+}
+
+pub(crate) fn enabled(metadata: &::log::Metadata) -> bool {
+    metadata.level() <= ::log::max_level()
+        && with_memory(
+            |memory| matches!(memory.as_ref(), Some(memory) if memory.filters.is_enabled(metadata)),
+        )
+        .unwrap_or(false)
+}
+
+pub(crate) fn log(record: &::log::Record) {
+    if record.level() > ::log::max_level() {
+        return;
+    }
+    with_memory(|memory| {
+        if let Some(memory) = memory {
+            if memory.filters.is_enabled(record.metadata()) {
+                memory.write(format_args!(
+                    "[{} {}]: {}\r\n",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ));
+            }
+        }
+    });
+}
+
+pub(crate) fn print(args: fmt::Arguments) {
+    with_memory(|memory| {
+        if let Some(memory) = memory {
+            memory.write(args);
+        }
+    });
+}
+
+/// Initialize the memory logger with a buffer
+///
+/// Returns an error if you've already called `init()`, and you haven't
+/// [`release()`](fn.release.html)d the logger. Also returns an error if you've already
+/// specified a logger through another interface.
+///
+/// If another logger in this crate is initialized, this logger replaces it. See
+/// [Switching loggers](../index.html#switching-loggers) for more information.
+pub fn init(buffer: &'static mut [u8], config: LoggingConfig) -> Result<(), SetLoggerError> {
+    init_storage(Storage::volatile(buffer), config)
+}
+
+pub(crate) fn init_storage(
+    storage: Storage<&'static mut [u8]>,
+    config: LoggingConfig,
+) -> Result<(), SetLoggerError> {
+    critical_section::with(move |_| {
+        let mut memory = MEMORY.try_lock().ok_or(SetLoggerError(()))?;
+        if memory.is_some() {
+            return Err(SetLoggerError(()));
+        }
+
+        dispatch::activate(Backend::Memory, config.max_level)?;
+        // Replace the previous boot's header before the replay overwrites its records
+        let mut records = Records::new(storage);
+        Storage::save(&mut records);
+        early::replay(|bytes| Storage::write_bytes(&mut records, bytes));
+        let memory = memory.get_or_insert(Memory {
+            records,
+            filters: Filters(config.filters),
+        });
+        memory.save();
+        Ok(())
+    })
+}
+
+/// Copy the oldest logs into `buffer`, and remove them from the logger
+///
+/// Returns the number of bytes copied into `buffer`. Returns 0 if there are no logs, if
+/// there is no logger, or if `read()` preempted a context that's logging.
+pub fn read(buffer: &mut [u8]) -> usize {
+    with_memory(|memory| {
+        let memory = match memory {
+            Some(memory) => memory,
+            None => return 0,
+        };
+        let ring = memory.records.complete();
+        let (first, second) = ring.as_slices();
+        let mut len = 0;
+        for slice in [first, second].iter() {
+            let count = slice.len().min(buffer.len() - len);
+            buffer[len..len + count].copy_from_slice(&slice[..count]);
+            len += count;
+        }
+        ring.consume(len);
+        memory.save();
+        len
+    })
+    .unwrap_or(0)
+}
+
+/// Run `f` with the logs, without removing them from the logger
+///
+/// The logs may wrap around the end of the buffer. `f` receives the oldest logs in the
+/// first slice, and the newest logs in the second slice. `f` runs in a critical section.
+///
+/// Returns `None` if there is no logger, or if `contents()` preempted a context that's logging.
+pub fn contents<R>(f: impl FnOnce(&[u8], &[u8]) -> R) -> Option<R> {
+    with_memory(|memory| {
+        let ring = memory.as_mut()?.records.complete();
+        let (first, second) = ring.as_slices();
+        Some(f(first, second))
+    })
+    .flatten()
+}
+
+/// Remove all logs from the logger
+pub fn clear() {
+    with_memory(|memory| {
+        if let Some(memory) = memory {
+            memory.records.complete().clear();
+            memory.save();
+        }
+    });
+}
+
+/// Release the memory logger's buffer
+///
+/// Returns the buffer that you supplied to `init()`. After `release()`, the logger discards
+/// all messages until you call `init()` again. Returns `None` if there is no logger, or if
+/// `release()` preempted a context that's logging.
+pub fn release() -> Option<&'static mut [u8]> {
+    with_memory(|memory| memory.take())
+        .flatten()
+        .map(|memory| memory.records.into_inner().into_inner())
+}
+
+/// Write `args` into the buffer, even if another context is writing
+///
+/// Returns `false` if there is no memory logger.
+#[cfg(any(feature = "panic-handler", feature = "fault"))]
+pub(crate) fn emergency_write(args: core::fmt::Arguments) -> bool {
+    critical_section::with(|_| {
+        // Safety: caller never returns to the context that holds the lock
+        let mut memory = unsafe { MEMORY.steal() };
+        if let Some(memory) = memory.as_mut() {
+            memory.write(args);
+            true
+        } else {
+            false
+        }
+    })
+}
@@ -0,0 +1,72 @@
+//! Short copies of targets and file names
+
+use core::{fmt, str};
+
+/// The number of bytes that a name keeps
+const LEN: usize = 32;
+
+/// The first bytes of a target, or a file name
+///
+/// Summaries use names after the record is gone.
+#[derive(Clone, Copy)]
+pub struct Name {
+    bytes: [u8; LEN],
+    len: u8,
+}
+
+impl Name {
+    pub const fn new() -> Self {
+        Name {
+            bytes: [0; LEN],
+            len: 0,
+        }
+    }
+
+    /// Copy the whole characters that fit in the first 32 bytes of `name`
+    pub fn truncate(name: &str) -> Self {
+        let mut len = name.len().min(LEN);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; LEN];
+        bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+        Name {
+            bytes,
+            len: len as u8,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Never fails; truncate() copies whole characters
+        str::from_utf8(&self.bytes[..usize::from(self.len)]).unwrap_or("")
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Name;
+
+    #[test]
+    fn short_names() {
+        assert_eq!(Name::new().as_str(), "");
+        assert_eq!(Name::truncate("usb").as_str(), "usb");
+    }
+
+    #[test]
+    fn truncate_long_names() {
+        assert_eq!(
+            Name::truncate("a_very_long_crate_name::with::a_deep::module").as_str(),
+            "a_very_long_crate_name::with::a_"
+        );
+        assert_eq!(
+            Name::truncate("0123456789012345678901234567890é").as_str(),
+            "0123456789012345678901234567890"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! One-time initialization
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+/// Runs an initialization routine once
+///
+/// `Once` is an atomic state machine. Unlike `std::sync::Once`, a caller never waits for
+/// another caller's initialization. An interrupt that preempts an initialization cannot
+/// wait for that initialization to finish, since the preempted initialization cannot run
+/// until the interrupt returns.
+pub struct Once {
+    state: AtomicU8,
+}
+
+/// Describes why [`call_once()`](struct.Once.html#method.call_once) did not complete
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Another caller is running the initialization routine
+    Running,
+    /// The initialization routine failed
+    Failed(E),
+}
+
+impl Once {
+    pub const fn new() -> Self {
+        Once {
+            state: AtomicU8::new(INCOMPLETE),
+        }
+    }
+
+    /// Run `f`, unless another call completed
+    ///
+    /// - If a previous call completed, returns `Ok(())` without running `f`.
+    /// - If another call is running `f`, returns `Error::Running` without running `f`.
+    /// - If `f` fails, `call_once()` returns `f`'s error, and a later call may try again.
+    pub fn call_once<E>(&self, f: impl FnOnce() -> Result<(), E>) -> Result<(), Error<E>> {
+        match self
+            .state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire)
+        {
+            Ok(_) => match f() {
+                Ok(()) => {
+                    self.state.store(COMPLETE, Ordering::Release);
+                    Ok(())
+                }
+                Err(err) => {
+                    self.state.store(INCOMPLETE, Ordering::Release);
+                    Err(Error::Failed(err))
+                }
+            },
+            Err(COMPLETE) => Ok(()),
+            Err(_) => Err(Error::Running),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::{Error, Once};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn double_init() {
+        let once = Once::new();
+        let mut calls = 0;
+        let mut init = || -> Result<(), ()> {
+            calls += 1;
+            Ok(())
+        };
+        assert_eq!(once.call_once(&mut init), Ok(()));
+        assert_eq!(once.call_once(&mut init), Ok(()));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn failed_init_may_retry() {
+        let once = Once::new();
+        assert_eq!(once.call_once(|| Err(5)), Err(Error::Failed(5)));
+        assert_eq!(once.call_once(|| Err(7)), Err(Error::Failed(7)));
+        assert_eq!(once.call_once(|| -> Result<(), i32> { Ok(()) }), Ok(()));
+        assert_eq!(once.call_once(|| Err(9)), Ok(()));
+    }
+
+    #[test]
+    fn init_from_interrupt() {
+        // Simulate an interrupt that preempts an initialization, and
+        // tries to initialize
+        let once = Once::new();
+        let mut preempted = None;
+        let result = once.call_once(|| -> Result<(), ()> {
+            preempted = Some(once.call_once(|| -> Result<(), ()> { unreachable!() }));
+            Ok(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(preempted, Some(Err(Error::Running)));
+    }
+
+    #[test]
+    fn racing_init() {
+        static ONCE: Once = Once::new();
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        let threads: std::vec::Vec<_> = (0..8)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    ONCE.call_once(|| -> Result<(), ()> {
+                        CALLS.fetch_add(1, Ordering::SeqCst);
+                        Ok(())
+                    })
+                })
+            })
+            .collect();
+        for thread in threads {
+            // Racing callers either see the completed initialization,
+            // or they see that it's running.
+            let result = thread.join().unwrap();
+            assert!(result == Ok(()) || result == Err(Error::Running));
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+}
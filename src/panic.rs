@@ -0,0 +1,17 @@
+//! A panic handler that reports through the active logger
+
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{self, Ordering},
+};
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+
+    crate::dispatch::emergency_write(format_args!("[PANIC]: {}\r\n", info));
+
+    loop {
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,30 @@
+//! The DMA loggers' polling result
+
+/// A [`poll()`](fn.poll.html)ing result
+///
+/// `Poll` provides insight into the DMA logger's state
+#[derive(Debug, PartialEq, Eq)]
+pub enum Poll {
+    /// There is an active transfer
+    ///
+    /// The next log message will be scheduled after the active transfer
+    /// completes. The transfer will start on either the next call to
+    /// [`poll()`](fn.poll.html), or along with the next written log message.
+    ///
+    /// An `Active` return could mean that
+    ///
+    /// - `poll()` was called while there was an active transfer, and nothing
+    ///   happened.
+    /// - `poll()` was called, and an active transfer is now complete. `poll()` scheduled
+    ///   another transfer after detecting data in the circular buffer.
+    Active,
+    /// There is no active transfer, and the logger is idle
+    ///
+    /// The next log message will be scheduled immediately. An `Idle` result could
+    /// mean that
+    ///
+    /// - `poll()` was called when there was no active transfer.
+    /// - `poll()` was called, and an active transfer is now complete. There was no other
+    ///   log message in the circular buffer, so there's nothing to do.
+    Idle,
+}
@@ -0,0 +1,289 @@
+//! Limit the rate of log records
+//!
+//! A misbehaving interrupt handler can log thousands of records per second, filling the
+//! UART, or the DMA buffer, and starving all other records. The rate limiter caps the
+//! number of records that each target, or each call site, logs in a window. It drops the
+//! rest, and counts them. When the window ends, it writes a summary for each key that it
+//! limited:
+//!
+//! ```text
+//! [WARN sensor::isr]: suppressed 9990 messages
+//! ```
+//!
+//! The limiter doesn't keep time. Call [`tick()`](fn.tick.html) to end each window; for
+//! instance, call `tick()` once a second from a timer's interrupt handler. The limiter
+//! works with every logger in this crate, and it keeps its state when you switch loggers.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use imxrt_uart_log::ratelimit::{self, Key, RateLimit, Slot};
+//!
+//! let slots = cortex_m::singleton!(: [Slot; 8] = [Slot::new(); 8]).unwrap();
+//! ratelimit::init(
+//!     slots,
+//!     RateLimit {
+//!         records: 100,
+//!         key: Key::Target,
+//!     },
+//! )
+//! .unwrap();
+//!
+//! // Once a second, in a timer interrupt...
+//! ratelimit::tick();
+//! ```
+//!
+//! # Implementation
+//!
+//! The limiter tracks each key in a [`Slot`](struct.Slot.html) that you supply. It compares
+//! keys by hash, so two keys may share a slot. If there's no free slot, the limiter doesn't
+//! limit the key's records. `tick()` frees all slots. Supply one slot for each target, or
+//! call site, that you expect to log in a window.
+//!
+//! The limiter only counts records that the logger would write. The summary names the first
+//! 32 bytes of the target, or the file, and it bypasses the logger's filters.
+
+use crate::{dispatch, fnv::Fnv, name::Name, SetLoggerError};
+use core::{cell::RefCell, fmt, mem};
+use critical_section::Mutex;
+
+/// How the limiter groups records
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// Limit each target
+    Target,
+    /// Limit each call site, identified by the record's file and line
+    CallSite,
+}
+
+/// Rate limiter configuration
+pub struct RateLimit {
+    /// The number of records that each key may log in a window
+    pub records: u32,
+    /// How the limiter groups records
+    pub key: Key,
+}
+
+/// The limiter's state for one key
+///
+/// Create slots with [`new()`](#method.new), and supply them to [`init()`](fn.init.html).
+#[derive(Clone, Copy)]
+pub struct Slot {
+    /// The key's hash, or `None` if the slot is free
+    hash: Option<u32>,
+    name: Name,
+    line: Option<u32>,
+    /// The number of records logged in this window
+    records: u32,
+    /// The number of records dropped in this window
+    suppressed: u32,
+}
+
+impl Slot {
+    /// Create a free slot
+    pub const fn new() -> Self {
+        Slot {
+            hash: None,
+            name: Name::new(),
+            line: None,
+            records: 0,
+            suppressed: 0,
+        }
+    }
+
+    fn claim(&mut self, hash: u32, name: &str, line: Option<u32>) {
+        *self = Slot {
+            hash: Some(hash),
+            name: Name::truncate(name),
+            line,
+            ..Slot::new()
+        };
+    }
+}
+
+impl Default for Slot {
+    fn default() -> Self {
+        Slot::new()
+    }
+}
+
+/// Formats a slot's summary
+struct Summary<'a>(&'a Slot);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[WARN {}", self.0.name)?;
+        if let Some(line) = self.0.line {
+            write!(f, ":{}", line)?;
+        }
+        write!(f, "]: suppressed {} messages\r\n", self.0.suppressed)
+    }
+}
+
+struct Limiter {
+    slots: &'static mut [Slot],
+    config: RateLimit,
+}
+
+impl Limiter {
+    /// Returns `true` if the record is within its key's limit
+    fn admit(&mut self, record: &::log::Record) -> bool {
+        let mut fnv = Fnv::new();
+        let (name, line) = match self.config.key {
+            Key::Target => (record.target(), None),
+            Key::CallSite => (record.file().unwrap_or(""), record.line()),
+        };
+        fnv.write(name.as_bytes());
+        if let Some(line) = line {
+            fnv.write(&line.to_le_bytes());
+        }
+        let hash = fnv.finish();
+
+        let slot = match self.slots.iter().position(|slot| slot.hash == Some(hash)) {
+            Some(idx) => &mut self.slots[idx],
+            None => match self.slots.iter_mut().find(|slot| slot.hash.is_none()) {
+                Some(slot) => {
+                    slot.claim(hash, name, line);
+                    slot
+                }
+                // No room to track this key
+                None => return true,
+            },
+        };
+
+        if slot.records < self.config.records {
+            slot.records += 1;
+            true
+        } else {
+            slot.suppressed = slot.suppressed.saturating_add(1);
+            false
+        }
+    }
+
+    /// Free the slot at `idx`, and return its state
+    fn end_window(&mut self, idx: usize) -> Option<Slot> {
+        self.slots.get_mut(idx).map(mem::take)
+    }
+}
+
+/// The limiter, if initialized
+static LIMITER: Mutex<RefCell<Option<Limiter>>> = Mutex::new(RefCell::new(None));
+
+/// Returns `true` if there's a limiter
+pub(crate) fn is_enabled() -> bool {
+    critical_section::with(|cs| LIMITER.borrow(cs).borrow().is_some())
+}
+
+/// Returns `true` if the dispatcher should log `record`
+///
+/// Only call this for records that the active logger would write. The limiter
+/// only counts those records.
+pub(crate) fn admit(record: &::log::Record) -> bool {
+    critical_section::with(|cs| match LIMITER.borrow(cs).borrow_mut().as_mut() {
+        Some(limiter) => limiter.admit(record),
+        None => true,
+    })
+}
+
+/// Initialize the rate limiter with a collection of slots
+///
+/// Returns an error if you've already called `init()`.
+pub fn init(slots: &'static mut [Slot], config: RateLimit) -> Result<(), SetLoggerError> {
+    critical_section::with(move |cs| {
+        let mut limiter = LIMITER.borrow(cs).borrow_mut();
+        if limiter.is_some() {
+            return Err(SetLoggerError(()));
+        }
+        *limiter = Some(Limiter { slots, config });
+        Ok(())
+    })
+}
+
+/// End the rate limiter's window
+///
+/// `tick()` writes a summary for each key that the limiter limited, then starts a new window.
+/// The summaries go to the active logger. If there's no limiter, `tick()` does nothing.
+pub fn tick() {
+    let mut idx = 0;
+    // Write each summary outside of the critical section
+    while let Some(slot) = critical_section::with(|cs| {
+        LIMITER
+            .borrow(cs)
+            .borrow_mut()
+            .as_mut()
+            .and_then(|limiter| limiter.end_window(idx))
+    }) {
+        if slot.suppressed > 0 {
+            dispatch::print(format_args!("{}", Summary(&slot)));
+        }
+        idx += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::{Key, Limiter, RateLimit, Slot, Summary};
+    use std::{boxed::Box, format, vec};
+
+    fn limiter(records: u32, key: Key, slots: usize) -> Limiter {
+        Limiter {
+            slots: Box::leak(vec![Slot::new(); slots].into_boxed_slice()),
+            config: RateLimit { records, key },
+        }
+    }
+
+    fn admit(limiter: &mut Limiter, target: &str, line: u32) -> bool {
+        limiter.admit(
+            &::log::Record::builder()
+                .level(::log::Level::Info)
+                .target(target)
+                .file(Some("src/sensor.rs"))
+                .line(Some(line))
+                .build(),
+        )
+    }
+
+    #[test]
+    fn limit_each_target() {
+        let mut limiter = limiter(2, Key::Target, 4);
+        assert!(admit(&mut limiter, "usb", 1));
+        assert!(admit(&mut limiter, "usb", 2));
+        assert!(!admit(&mut limiter, "usb", 3));
+        assert!(!admit(&mut limiter, "usb", 4));
+        assert!(admit(&mut limiter, "i2c", 5));
+
+        let slot = limiter.end_window(0).unwrap();
+        assert_eq!(
+            format!("{}", Summary(&slot)),
+            "[WARN usb]: suppressed 2 messages\r\n"
+        );
+        assert_eq!(limiter.end_window(1).unwrap().suppressed, 0);
+        assert!(limiter.end_window(4).is_none());
+
+        // New window
+        assert!(admit(&mut limiter, "usb", 6));
+    }
+
+    #[test]
+    fn limit_each_call_site() {
+        let mut limiter = limiter(1, Key::CallSite, 4);
+        assert!(admit(&mut limiter, "sensor", 10));
+        assert!(!admit(&mut limiter, "sensor", 10));
+        assert!(admit(&mut limiter, "sensor", 11));
+
+        let slot = limiter.end_window(0).unwrap();
+        assert_eq!(
+            format!("{}", Summary(&slot)),
+            "[WARN src/sensor.rs:10]: suppressed 1 messages\r\n"
+        );
+    }
+
+    #[test]
+    fn no_free_slots() {
+        let mut limiter = limiter(1, Key::Target, 1);
+        assert!(admit(&mut limiter, "usb", 1));
+        assert!(admit(&mut limiter, "i2c", 2));
+        assert!(admit(&mut limiter, "i2c", 3));
+    }
+}
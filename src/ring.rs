@@ -0,0 +1,426 @@
+//! A byte ring that overwrites its oldest data
+
+use core::fmt;
+
+/// A fixed-capacity byte queue
+///
+/// When there's no space for new data, `Ring` discards the oldest data.
+/// `B` is the backing storage, like an array or a `&'static mut [u8]`.
+pub struct Ring<B> {
+    buffer: B,
+    /// Index of the oldest byte
+    start: usize,
+    /// Number of bytes in the ring
+    len: usize,
+}
+
+impl<B> Ring<B> {
+    pub const fn new(buffer: B) -> Self {
+        Ring {
+            buffer,
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the backing storage
+    #[cfg(feature = "lpuart")]
+    pub fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+impl<B: AsRef<[u8]>> Ring<B> {
+    /// Create a ring that already holds `len` bytes, starting at `start`
+    ///
+    /// Returns `None` if `start` and `len` are not valid for `buffer`.
+    pub fn from_parts(buffer: B, start: usize, len: usize) -> Option<Self> {
+        let capacity = buffer.as_ref().len();
+        if len > capacity || (start >= capacity && capacity > 0) || (start > 0 && capacity == 0) {
+            None
+        } else {
+            Some(Ring { buffer, start, len })
+        }
+    }
+
+    /// Returns the index of the oldest byte, and the number of bytes
+    pub fn parts(&self) -> (usize, usize) {
+        (self.start, self.len)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.as_ref().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the byte at `idx`, counting from the oldest byte
+    fn get(&self, idx: usize) -> Option<u8> {
+        if idx < self.len {
+            Some(self.buffer.as_ref()[(self.start + idx) % self.capacity()])
+        } else {
+            None
+        }
+    }
+
+    /// Discard all data
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+
+    /// Discard the oldest `count` bytes
+    pub fn consume(&mut self, count: usize) {
+        let count = count.min(self.len);
+        if count == self.len {
+            self.clear();
+        } else {
+            self.start = (self.start + count) % self.capacity();
+            self.len -= count;
+        }
+    }
+
+    /// Returns the data, oldest first
+    ///
+    /// The second slice is non-empty if the data wraps around the end of the buffer.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let buffer = self.buffer.as_ref();
+        let end = self.start + self.len;
+        if end <= buffer.len() {
+            (&buffer[self.start..end], &[])
+        } else {
+            (&buffer[self.start..], &buffer[..end - buffer.len()])
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Ring<B> {
+    /// Append `bytes`, overwriting the oldest data if there's no space
+    ///
+    /// Returns the number of bytes that were overwritten, or that did not fit.
+    pub fn write(&mut self, bytes: &[u8]) -> usize {
+        let overwritten = (self.len + bytes.len()).saturating_sub(self.capacity());
+        let skip = self.make_room(bytes.len());
+        self.append(&bytes[skip..]);
+        overwritten
+    }
+
+    /// Discard the oldest data, so that `count` new bytes fit
+    ///
+    /// Returns the number of the new bytes that don't fit, since only the newest data fits.
+    fn make_room(&mut self, count: usize) -> usize {
+        let capacity = self.capacity();
+        if count > capacity {
+            self.clear();
+            count - capacity
+        } else {
+            self.consume((self.len + count).saturating_sub(capacity));
+            0
+        }
+    }
+
+    /// Append as much of `bytes` as fits, without overwriting any data
+    ///
+    /// Returns the number of bytes appended.
+    // Only the LPUART loggers push
+    #[cfg_attr(not(feature = "lpuart"), allow(dead_code))]
+    pub fn push(&mut self, bytes: &[u8]) -> usize {
+        let count = bytes.len().min(self.capacity() - self.len);
+        self.append(&bytes[..count]);
+        count
+    }
+
+    /// Append a formatted record if it fits, without overwriting any data
+    ///
+    /// Returns `false` if the record doesn't fit. The ring drops the whole record.
+    #[cfg(feature = "lpuart")]
+    pub fn push_fmt(&mut self, args: fmt::Arguments) -> bool {
+        struct Push<'a, B> {
+            ring: &'a mut Ring<B>,
+            fits: bool,
+        }
+
+        impl<B: AsRef<[u8]> + AsMut<[u8]>> fmt::Write for Push<'_, B> {
+            fn write_str(&mut self, string: &str) -> fmt::Result {
+                self.fits = self.fits && self.ring.push(string.as_bytes()) == string.len();
+                Ok(())
+            }
+        }
+
+        let len = self.len;
+        let mut push = Push {
+            ring: self,
+            fits: true,
+        };
+        // Never fails
+        let _ = fmt::write(&mut push, args);
+        let fits = push.fits;
+        if !fits {
+            self.len = len;
+        }
+        fits
+    }
+
+    /// Append `bytes`, which must fit in the free space
+    fn append(&mut self, bytes: &[u8]) {
+        let capacity = self.capacity();
+        let end = (self.start + self.len) % capacity.max(1);
+        let buffer = self.buffer.as_mut();
+        let (first, second) = bytes.split_at(bytes.len().min(capacity - end));
+        buffer[end..end + first.len()].copy_from_slice(first);
+        buffer[..second.len()].copy_from_slice(second);
+        self.len += bytes.len();
+    }
+}
+
+/// Formatted log records in a `Ring`
+///
+/// If the ring overwrites data, the oldest record may be incomplete.
+/// `Records` discards incomplete records before you read the ring.
+pub struct Records<B> {
+    ring: Ring<B>,
+    /// `true` if the oldest byte is in the middle of a record
+    truncated: bool,
+}
+
+impl<B> Records<B> {
+    pub const fn new(buffer: B) -> Self {
+        Records {
+            ring: Ring::new(buffer),
+            truncated: false,
+        }
+    }
+
+    /// Returns the backing storage
+    pub fn into_inner(self) -> B {
+        self.ring.buffer
+    }
+
+    pub fn get_mut(&mut self) -> &mut B {
+        &mut self.ring.buffer
+    }
+}
+
+impl<B: AsRef<[u8]>> Records<B> {
+    /// Create records from a ring's parts, and its truncated flag
+    ///
+    /// Returns `None` if the parts are not valid for `buffer`.
+    pub fn from_parts(buffer: B, start: usize, len: usize, truncated: bool) -> Option<Self> {
+        Ring::from_parts(buffer, start, len).map(|ring| Records { ring, truncated })
+    }
+
+    /// Returns the ring's parts, and the truncated flag
+    pub fn parts(&self) -> (usize, usize, bool) {
+        let (start, len) = self.ring.parts();
+        (start, len, self.truncated)
+    }
+
+    /// Returns the ring, starting with the oldest complete record
+    pub fn complete(&mut self) -> &mut Ring<B> {
+        if self.truncated {
+            skip_partial(&mut self.ring);
+            self.truncated = false;
+        }
+        &mut self.ring
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Records<B> {
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_bytes_with(bytes, |_| {});
+    }
+
+    /// Append `bytes`, and call `discarded` before the bytes overwrite any data
+    ///
+    /// `discarded` sees the records after the ring discards the data that `bytes`
+    /// overwrites, and before the ring appends `bytes`. It only runs if `bytes` overwrites
+    /// data.
+    pub fn write_bytes_with(&mut self, bytes: &[u8], discarded: impl FnOnce(&mut Self)) {
+        let overwritten = (self.ring.len + bytes.len()).saturating_sub(self.ring.capacity());
+        if overwritten == 0 {
+            self.ring.write(bytes);
+            return;
+        }
+        // The last discarded byte is either old data, or the start of `bytes`.
+        // The oldest byte starts a record if the last discarded byte ended one.
+        let last = self
+            .ring
+            .get(overwritten - 1)
+            .or_else(|| bytes.get(overwritten - 1 - self.ring.len).copied());
+        self.truncated = last != Some(b'\n');
+        let skip = self.ring.make_room(bytes.len());
+        discarded(self);
+        // There's room, so this doesn't overwrite anything
+        self.ring.write(&bytes[skip..]);
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> fmt::Write for Records<B> {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.write_bytes(string.as_bytes());
+        Ok(())
+    }
+}
+
+/// Discard data up to, and including, the first newline
+fn skip_partial<B: AsRef<[u8]>>(ring: &mut Ring<B>) {
+    let (first, second) = ring.as_slices();
+    let newline = first
+        .iter()
+        .chain(second.iter())
+        .position(|&byte| byte == b'\n');
+    match newline {
+        Some(idx) => ring.consume(idx + 1),
+        None => ring.clear(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Records, Ring};
+
+    fn contents<B: AsRef<[u8]>>(ring: &Ring<B>) -> ([u8; 16], usize) {
+        let (first, second) = ring.as_slices();
+        let mut out = [0; 16];
+        out[..first.len()].copy_from_slice(first);
+        out[first.len()..first.len() + second.len()].copy_from_slice(second);
+        (out, first.len() + second.len())
+    }
+
+    #[test]
+    fn write_read() {
+        let mut ring = Ring::new([0; 8]);
+        assert!(ring.is_empty());
+        assert_eq!(ring.write(b"abc"), 0);
+        assert_eq!(ring.write(b"de"), 0);
+        let (out, len) = contents(&ring);
+        assert_eq!(&out[..len], b"abcde");
+    }
+
+    #[test]
+    fn overwrite_oldest() {
+        let mut ring = Ring::new([0; 8]);
+        ring.write(b"abcdef");
+        assert_eq!(ring.write(b"ghij"), 2);
+        let (first, second) = ring.as_slices();
+        assert_eq!(first, b"cdefgh");
+        assert_eq!(second, b"ij");
+    }
+
+    #[test]
+    fn larger_than_capacity() {
+        let mut ring = Ring::new([0; 4]);
+        ring.write(b"ab");
+        assert_eq!(ring.write(b"cdefgh"), 4);
+        let (out, len) = contents(&ring);
+        assert_eq!(&out[..len], b"efgh");
+    }
+
+    #[test]
+    fn consume() {
+        let mut ring = Ring::new([0; 4]);
+        ring.write(b"abc");
+        ring.consume(2);
+        ring.write(b"def");
+        let (out, len) = contents(&ring);
+        assert_eq!(&out[..len], b"cdef");
+        ring.consume(10);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn push_without_overwriting() {
+        let mut ring = Ring::new([0; 8]);
+        assert_eq!(ring.push(b"abcdef"), 6);
+        ring.consume(2);
+        assert_eq!(ring.push(b"ghijkl"), 4);
+        let (first, second) = ring.as_slices();
+        assert_eq!(first, b"cdefgh");
+        assert_eq!(second, b"ij");
+        assert_eq!(ring.push(b"k"), 0);
+    }
+
+    #[cfg(feature = "lpuart")]
+    #[test]
+    fn drop_records_that_dont_fit() {
+        let mut ring = Ring::new([0; 8]);
+        assert!(ring.push_fmt(format_args!("{}{}", "ab", "cd")));
+        assert!(!ring.push_fmt(format_args!("{}{}", "ef", "ghi")));
+        assert_eq!(ring.as_slices(), (&b"abcd"[..], &b""[..]));
+
+        // The next record may use the dropped record's space
+        assert!(ring.push_fmt(format_args!("{}", "efg")));
+        ring.consume(6);
+        assert!(ring.push_fmt(format_args!("{}", "hijkl")));
+        let (first, second) = ring.as_slices();
+        assert_eq!(first, b"gh");
+        assert_eq!(second, b"ijkl");
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut ring = Ring::new([0; 0]);
+        assert_eq!(ring.write(b"abc"), 3);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn skip_partial_record() {
+        let mut records = Records::new([0; 16]);
+        records.write_bytes(b"[INFO a]: 1\r\n");
+        records.write_bytes(b"[INFO a]: 2\r\n");
+        let (out, len) = contents(records.complete());
+        assert_eq!(&out[..len], b"[INFO a]: 2\r\n");
+    }
+
+    #[test]
+    fn skip_partial_without_newline() {
+        let mut records = Records::new([0; 4]);
+        records.write_bytes(b"[INFO a]: 1");
+        assert!(records.complete().is_empty());
+    }
+
+    #[test]
+    fn overwrite_whole_record() {
+        let mut records = Records::new([0; 16]);
+        records.write_bytes(b"[I]: 1\r\n");
+        records.write_bytes(b"[I]: 2\r\n");
+        records.write_bytes(b"[I]: 3\r\n");
+        let (out, len) = contents(records.complete());
+        assert_eq!(&out[..len], b"[I]: 2\r\n[I]: 3\r\n");
+    }
+
+    #[test]
+    fn overwrite_partial_record_in_pieces() {
+        let mut records = Records::new([0; 16]);
+        records.write_bytes(b"[I]: 1\r\n");
+        records.write_bytes(b"[I]: 2\r\n");
+        records.write_bytes(b"[I]: ");
+        records.write_bytes(b"3\r\n");
+        let (out, len) = contents(records.complete());
+        assert_eq!(&out[..len], b"[I]: 2\r\n[I]: 3\r\n");
+
+        // The next record overwrites part of the oldest record
+        records.write_bytes(b"[I]: 4");
+        let (out, len) = contents(records.complete());
+        assert_eq!(&out[..len], b"[I]: 3\r\n[I]: 4");
+    }
+
+    #[test]
+    fn keep_complete_records() {
+        let mut records = Records::new([0; 16]);
+        records.write_bytes(b"[INFO a]: 1\r\n");
+        let (out, len) = contents(records.complete());
+        assert_eq!(&out[..len], b"[INFO a]: 1\r\n");
+    }
+
+    #[test]
+    fn from_parts() {
+        assert!(Ring::from_parts([0; 4], 3, 4).is_some());
+        assert!(Ring::from_parts([0; 4], 4, 0).is_none());
+        assert!(Ring::from_parts([0; 4], 0, 5).is_none());
+        assert!(Ring::from_parts([0; 0], 0, 0).is_some());
+    }
+}
@@ -0,0 +1,265 @@
+//! DMA logging for RTIC applications
+//!
+//! The [DMA](../dma/index.html) logger keeps its UART, DMA channel, and buffer in a global
+//! mutex, and every `log` call, and every [`dma::poll()`](../dma/fn.poll.html), takes that
+//! mutex. In an [RTIC](https://rtic.rs) application, you may prefer to keep the peripherals
+//! in a resource. The RTIC logger splits the DMA logger in two:
+//!
+//! - the `log` macros copy each record into a queue, and pend the DMA channel's interrupt.
+//! - a [`Logger`](struct.Logger.html) owns the UART, DMA channel, and DMA buffer. Keep it in a
+//!   resource of the hardware task that's bound to the DMA channel's interrupt, and call
+//!   [`Logger::poll()`](struct.Logger.html#method.poll) from that task. `poll()` moves queued
+//!   records into the DMA buffer, and schedules transfers.
+//!
+//! The hardware task is the only context that touches the peripherals, so you don't need
+//! to lock the logger in any other task.
+//!
+//! 1. Configure a UART peripheral, and a DMA channel. Enable the channel's interrupt on
+//!    completion.
+//! 2. Call [`init`](fn.init.html) with a queue, the DMA channel's interrupt, and a logging
+//!    configuration.
+//! 3. Create a `Logger`, and return it as a resource from RTIC's `init`.
+//! 4. Bind a hardware task to the DMA channel's interrupt, and call `poll()` in that task.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use imxrt_hal::{dma::{Buffer, Channel, Circular}, iomuxc::consts::U2, ral::Interrupt, uart::Tx};
+//! use imxrt_uart_log::rtic::Logger;
+//! # fn uart2_tx() -> Tx<U2> { unimplemented!() }
+//! # fn dma_channel() -> Channel { unimplemented!() }
+//!
+//! #[repr(align(512))]
+//! struct Align(Buffer<[u8; 512]>);
+//! static BUFFER: Align = Align(Buffer::new([0; 512]));
+//!
+//! // In RTIC's init...
+//! let queue = cortex_m::singleton!(: [u8; 1024] = [0; 1024]).unwrap();
+//! imxrt_uart_log::rtic::init(queue, Interrupt::DMA7_DMA23, Default::default()).unwrap();
+//! let logger = Logger::new(uart2_tx(), dma_channel(), Circular::new(&BUFFER.0).unwrap());
+//! // ...return `logger` as a late resource.
+//!
+//! // In the task bound to DMA7_DMA23...
+//! # let mut logger = logger;
+//! logger.poll();
+//! ```
+//!
+//! See the `t4_rtic.rs` example for a complete RTIC application.
+//!
+//! # Implementation
+//!
+//! The `log` macros format each record into the queue with interrupts enabled. The queue is
+//! lock-free: the `log` macros add records, and `poll()` removes them, without a critical
+//! section. The logger **drops** a record if the record doesn't fit in the queue, or if it
+//! preempts another context that's logging. [`dropped()`](fn.dropped.html) counts the dropped
+//! records. `poll()` moves as many queued bytes as fit in the DMA buffer; the rest wait for the
+//! next `poll()`.
+//!
+//! The panic handler can't reach a `Logger` in an RTIC resource, so it writes nothing through
+//! the RTIC logger.
+
+use crate::{
+    blocking,
+    dispatch::{self, Backend},
+    dma::{self, IntoSink, Poll},
+    early,
+    lock::TryLock,
+    spsc::{self, Consumer, Indices},
+    Filters, LoggingConfig, SetLoggerError,
+};
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use cortex_m::peripheral::NVIC;
+use imxrt_hal::{
+    dma::{Channel, Circular},
+    ral::Interrupt,
+};
+
+/// Adds records to the queue
+struct Producer {
+    queue: spsc::Producer,
+    filters: Filters,
+    /// Runs the task that calls `poll()`
+    interrupt: Interrupt,
+}
+
+impl Producer {
+    /// Make the record visible to `poll()`, and run the task that calls `poll()`
+    fn commit(&mut self) {
+        if self.queue.commit() {
+            NVIC::pend(self.interrupt);
+        } else {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The queue's producer, if initialized
+///
+/// The lock serializes the contexts that log.
+static PRODUCER: TryLock<Option<Producer>> = TryLock::new(None);
+
+/// The queue's consumer, if initialized
+///
+/// The lock serializes `Logger`s.
+static CONSUMER: TryLock<Option<Consumer>> = TryLock::new(None);
+
+static INDICES: Indices = Indices::new();
+
+/// Number of records dropped by the logger
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Run `f` with the producer, if initialized
+///
+/// Counts a dropped record if another context holds the producer.
+fn with_producer(f: impl FnOnce(&mut Producer)) {
+    match PRODUCER.try_lock() {
+        Some(mut producer) => {
+            if let Some(producer) = producer.as_mut() {
+                f(producer);
+            }
+        }
+        None => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub(crate) fn enabled(metadata: &::log::Metadata) -> bool {
+    metadata.level() <= ::log::max_level()
+        && PRODUCER.try_lock().is_some_and(|producer| {
+            matches!(producer.as_ref(), Some(producer) if producer.filters.is_enabled(metadata))
+        })
+}
+
+pub(crate) fn log(record: &::log::Record) {
+    if record.level() > ::log::max_level() {
+        return;
+    }
+    with_producer(|producer| {
+        if producer.filters.is_enabled(record.metadata()) {
+            // Never fails
+            let _ = write!(
+                producer.queue,
+                "[{} {}]: {}\r\n",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            producer.commit();
+        }
+    })
+}
+
+pub(crate) fn print(args: fmt::Arguments) {
+    with_producer(|producer| {
+        // Never fails
+        let _ = producer.queue.write_fmt(args);
+        producer.commit();
+    })
+}
+
+/// Returns the number of records dropped by the RTIC logger
+///
+/// See [Implementation](index.html#implementation) to understand why the logger might drop
+/// records. The count wraps on overflow.
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Initialize the RTIC logger's queue
+///
+/// The logger enqueues records in `queue`, then pends `interrupt`. `interrupt` should be the
+/// DMA channel's interrupt, which runs the task that calls
+/// [`Logger::poll()`](struct.Logger.html#method.poll).
+///
+/// Returns an error if you've already called `init()`, or if you've already specified a logger
+/// through another interface.
+///
+/// If another logger in this crate is initialized, this logger replaces it. See
+/// [Switching loggers](../index.html#switching-loggers) for more information.
+pub fn init(
+    queue: &'static mut [u8],
+    interrupt: Interrupt,
+    config: LoggingConfig,
+) -> Result<(), SetLoggerError> {
+    critical_section::with(move |_| {
+        let mut producer = PRODUCER.try_lock().ok_or(SetLoggerError(()))?;
+        let mut consumer = CONSUMER.try_lock().ok_or(SetLoggerError(()))?;
+        if producer.is_some() {
+            return Err(SetLoggerError(()));
+        }
+
+        dispatch::activate(Backend::Rtic, config.max_level)?;
+        let (mut queue, queue_consumer) = spsc::split(queue, &INDICES);
+        early::replay(|bytes| {
+            queue.write(bytes);
+            queue.commit();
+        });
+        *producer = Some(Producer {
+            queue,
+            filters: Filters(config.filters),
+            interrupt,
+        });
+        *consumer = Some(queue_consumer);
+        NVIC::pend(interrupt);
+        Ok(())
+    })
+}
+
+/// The RTIC logger's UART, DMA channel, and DMA buffer
+///
+/// See the [module-level documentation](index.html) for more information.
+pub struct Logger {
+    inner: dma::Inner,
+}
+
+impl Logger {
+    /// Create a logger that transfers queued records to a UART
+    ///
+    /// `tx` should be an `imxrt_hal::uart::Tx` half, or a
+    /// [`blocking::Sink`](../blocking/enum.Sink.html).
+    pub fn new<T: IntoSink>(tx: T, channel: Channel, buffer: Circular<u8>) -> Self {
+        Logger {
+            inner: dma::Inner::new(tx, channel, buffer),
+        }
+    }
+
+    /// Move queued records into the DMA buffer, and drive DMA transfers
+    ///
+    /// Call `poll()` from the hardware task that's bound to the DMA channel's interrupt.
+    /// See [`Poll`](../dma/enum.Poll.html) for the meaning of the return.
+    pub fn poll(&mut self) -> Poll {
+        // Finish a completed transfer, so that there's room in the DMA buffer
+        self.inner.poll();
+        // Another Logger may be polling
+        if let Some(mut consumer) = CONSUMER.try_lock() {
+            if let Some(consumer) = consumer.as_mut() {
+                let (first, second) = consumer.as_slices();
+                let mut written = 0;
+                for slice in [first, second].iter().filter(|slice| !slice.is_empty()) {
+                    let accepted = self.inner.write_bytes(slice);
+                    written += accepted;
+                    if accepted < slice.len() {
+                        break;
+                    }
+                }
+                consumer.consume(written);
+            }
+        }
+        self.inner.poll()
+    }
+
+    /// Wait for all enqueued data to transfer, then release the peripherals
+    ///
+    /// Returns the UART transfer half, DMA channel, and DMA buffer that you supplied to
+    /// [`new()`](#method.new). Records that are still in the queue stay there for the next
+    /// `Logger`.
+    pub fn release(self) -> (blocking::Sink, Channel, Circular<u8>) {
+        self.inner
+            .release()
+            .expect("never switches to blocking writes")
+    }
+}
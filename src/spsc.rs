@@ -0,0 +1,233 @@
+//! A lock-free byte queue with one producer and one consumer
+//!
+//! The producer writes a record in pieces, then commits it. The consumer only sees committed
+//! records, so a record that doesn't fit is dropped whole.
+
+use core::{
+    fmt, ptr, slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The queue's positions, shared by the producer and the consumer
+pub struct Indices {
+    /// The end of the committed records; only the producer stores it
+    head: AtomicUsize,
+    /// The start of the committed records; only the consumer stores it
+    tail: AtomicUsize,
+}
+
+impl Indices {
+    pub const fn new() -> Self {
+        Indices {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Split `buffer` into a producer and a consumer
+///
+/// The queue holds one byte less than `buffer`, so that a full queue differs from an empty
+/// queue. Use each `indices` with only one buffer.
+pub fn split(buffer: &'static mut [u8], indices: &'static Indices) -> (Producer, Consumer) {
+    indices.head.store(0, Ordering::Relaxed);
+    indices.tail.store(0, Ordering::Relaxed);
+    let capacity = buffer.len();
+    let buffer = buffer.as_mut_ptr();
+    (
+        Producer {
+            buffer,
+            capacity,
+            indices,
+            pending: 0,
+            overflow: false,
+        },
+        Consumer {
+            buffer,
+            capacity,
+            indices,
+        },
+    )
+}
+
+/// Writes records into the queue
+pub struct Producer {
+    buffer: *mut u8,
+    capacity: usize,
+    indices: &'static Indices,
+    /// The end of the record that the producer is writing
+    pending: usize,
+    /// `true` if the record doesn't fit
+    overflow: bool,
+}
+
+// Safety: the producer only writes bytes that the consumer can't read.
+unsafe impl Send for Producer {}
+
+impl Producer {
+    /// Returns the number of bytes that the producer may write
+    fn free(&self) -> usize {
+        if self.capacity == 0 {
+            return 0;
+        }
+        let tail = self.indices.tail.load(Ordering::Acquire);
+        (tail + self.capacity - self.pending - 1) % self.capacity
+    }
+
+    /// Add `bytes` to the record
+    ///
+    /// If the record doesn't fit, [`commit()`](#method.commit) drops it.
+    pub fn write(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        if self.overflow || bytes.len() > self.free() {
+            self.overflow = true;
+            return;
+        }
+        let first = bytes.len().min(self.capacity - self.pending);
+        // Safety: the bytes from `pending` are free, and within the buffer. They may wrap
+        // around the end of the buffer.
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.buffer.add(self.pending), first);
+            ptr::copy_nonoverlapping(bytes[first..].as_ptr(), self.buffer, bytes.len() - first);
+        }
+        self.pending = (self.pending + bytes.len()) % self.capacity;
+    }
+
+    /// Make the record visible to the consumer
+    ///
+    /// Returns `false`, and drops the record, if the record didn't fit.
+    pub fn commit(&mut self) -> bool {
+        let fits = !self.overflow;
+        if fits {
+            self.indices.head.store(self.pending, Ordering::Release);
+        } else {
+            self.pending = self.indices.head.load(Ordering::Relaxed);
+        }
+        self.overflow = false;
+        fits
+    }
+}
+
+impl fmt::Write for Producer {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.write(string.as_bytes());
+        Ok(())
+    }
+}
+
+/// Reads committed records from the queue
+pub struct Consumer {
+    buffer: *const u8,
+    capacity: usize,
+    indices: &'static Indices,
+}
+
+// Safety: the consumer only reads bytes that the producer committed.
+unsafe impl Send for Consumer {}
+
+impl Consumer {
+    /// Returns the committed bytes
+    ///
+    /// The bytes may wrap around the end of the buffer. The first slice has the oldest bytes.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let head = self.indices.head.load(Ordering::Acquire);
+        let tail = self.indices.tail.load(Ordering::Relaxed);
+        // Safety: the producer doesn't write committed bytes until we consume them
+        unsafe {
+            let bytes = |start: usize, end: usize| {
+                slice::from_raw_parts(self.buffer.add(start), end - start)
+            };
+            if tail <= head {
+                (bytes(tail, head), &[])
+            } else {
+                (bytes(tail, self.capacity), bytes(0, head))
+            }
+        }
+    }
+
+    /// Remove the `count` oldest bytes
+    pub fn consume(&mut self, count: usize) {
+        let (first, second) = self.as_slices();
+        let count = count.min(first.len() + second.len());
+        if count > 0 {
+            let tail = self.indices.tail.load(Ordering::Relaxed);
+            self.indices
+                .tail
+                .store((tail + count) % self.capacity, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::{split, Consumer, Indices};
+    use std::{boxed::Box, vec::Vec};
+
+    fn queue(capacity: usize) -> (super::Producer, Consumer) {
+        split(
+            Box::leak(std::vec![0; capacity].into_boxed_slice()),
+            Box::leak(Box::new(Indices::new())),
+        )
+    }
+
+    fn contents(consumer: &Consumer) -> Vec<u8> {
+        let (first, second) = consumer.as_slices();
+        [first, second].concat()
+    }
+
+    #[test]
+    fn commit_records() {
+        let (mut producer, consumer) = queue(16);
+        producer.write(b"hello ");
+        assert!(contents(&consumer).is_empty());
+        producer.write(b"world");
+        assert!(producer.commit());
+        assert_eq!(contents(&consumer), b"hello world");
+    }
+
+    #[test]
+    fn drop_records_that_dont_fit() {
+        let (mut producer, mut consumer) = queue(8);
+        producer.write(b"abcd");
+        assert!(producer.commit());
+        producer.write(b"ef");
+        producer.write(b"ghi");
+        assert!(!producer.commit());
+        assert_eq!(contents(&consumer), b"abcd");
+
+        // The next record may use the dropped record's space
+        producer.write(b"xyz");
+        assert!(producer.commit());
+        assert_eq!(contents(&consumer), b"abcdxyz");
+
+        consumer.consume(4);
+        assert_eq!(contents(&consumer), b"xyz");
+    }
+
+    #[test]
+    fn wrap_around() {
+        let (mut producer, mut consumer) = queue(8);
+        producer.write(b"abcdef");
+        assert!(producer.commit());
+        consumer.consume(5);
+        producer.write(b"ghijk");
+        assert!(producer.commit());
+
+        let (first, second) = consumer.as_slices();
+        assert_eq!(first, b"fgh");
+        assert_eq!(second, b"ijk");
+        consumer.consume(100);
+        assert!(contents(&consumer).is_empty());
+    }
+
+    #[test]
+    fn empty_buffer() {
+        let (mut producer, consumer) = queue(0);
+        producer.write(b"a");
+        assert!(!producer.commit());
+        assert!(contents(&consumer).is_empty());
+    }
+}
@@ -0,0 +1,493 @@
+//! A logger that writes to multiple UARTs
+//!
+//! The tee logger writes every record to a collection of [`Output`](struct.Output.html)s.
+//! Each output has its own UART, and its own [`LoggingConfig`](../struct.LoggingConfig.html).
+//! An output may write like the [blocking](../blocking/index.html) logger, or like the
+//! [DMA](../dma/index.html) logger.
+//!
+//! 1. Configure your UARTs, and any DMA channels.
+//! 2. Create an `Output` for each UART, and place the outputs in a static collection.
+//! 3. Call [`init`](fn.init.html) with the outputs.
+//! 4. If you have DMA outputs, call [`poll`](fn.poll.html) from your DMA channels' interrupt
+//!    handlers, or throughout an event loop.
+//!
+//! # Example
+//!
+//! Log everything to a debug header on UART2, and log only warnings and errors to a
+//! diagnostics port on UART6. The diagnostics port uses DMA.
+//!
+//! ```no_run
+//! use imxrt_hal::dma::{Buffer, Channel, Circular};
+//! use imxrt_hal::{iomuxc::consts::{U2, U6}, uart::Tx};
+//! use imxrt_uart_log::{tee::Output, LoggingConfig};
+//! # fn uart2_tx() -> Tx<U2> { unimplemented!() }
+//! # fn uart6_tx() -> Tx<U6> { unimplemented!() }
+//! # fn dma_channel() -> Channel { unimplemented!() }
+//!
+//! #[repr(align(512))]
+//! struct Align(Buffer<[u8; 512]>);
+//! static BUFFER: Align = Align(Buffer::new([0; 512]));
+//!
+//! let outputs = cortex_m::singleton!(: [Output; 2] = [
+//!     Output::blocking(uart2_tx(), LoggingConfig::default()),
+//!     Output::dma(
+//!         uart6_tx(),
+//!         dma_channel(),
+//!         Circular::new(&BUFFER.0).unwrap(),
+//!         LoggingConfig {
+//!             max_level: log::LevelFilter::Warn,
+//!             filters: &[],
+//!         },
+//!     ),
+//! ])
+//! .unwrap();
+//!
+//! imxrt_uart_log::tee::init(outputs).unwrap();
+//! ```
+//!
+//! # Implementation
+//!
+//! The tee logger writes to all outputs in an interrupt free critical section, so a blocking
+//! output delays the DMA outputs, and the rest of the system. If an interrupt handler calls
+//! [`poll`](fn.poll.html) while another context is logging, `poll()` does nothing.
+//!
+//! In a panic or fault handler, every DMA output [switches to blocking
+//! writes](../dma/fn.switch_to_blocking.html).
+
+use crate::{
+    blocking,
+    dispatch::{self, Backend},
+    dma::{self, IntoSink, Poll},
+    early,
+    lock::TryLock,
+    timeout, Filters, LoggingConfig, SetLoggerError,
+};
+use core::fmt::{self, Write};
+use imxrt_hal::dma::{Channel, Circular};
+
+// There's no allocator to box the DMA state
+#[allow(clippy::large_enum_variant)]
+enum Kind {
+    Blocking(blocking::Sink),
+    Dma(dma::Inner),
+}
+
+/// A tee logger output
+///
+/// See the [module-level documentation](index.html) for more information.
+pub struct Output {
+    kind: Kind,
+    max_level: ::log::LevelFilter,
+    filters: Filters,
+}
+
+impl Output {
+    /// Create an output that blocks while it writes to a UART
+    ///
+    /// `tx` should be an `imxrt_hal::uart::Tx` half. The output respects the blocking
+    /// logger's [timeout](../blocking/fn.set_poll_timeout.html).
+    pub fn blocking<S: Into<blocking::Sink>>(tx: S, config: LoggingConfig) -> Self {
+        Output {
+            kind: Kind::Blocking(tx.into()),
+            max_level: config.max_level,
+            filters: Filters(config.filters),
+        }
+    }
+
+    /// Create an output that writes to a UART with DMA transfers
+    ///
+    /// `tx` should be an `imxrt_hal::uart::Tx` half. Each DMA output requires its own
+    /// DMA channel, and its own circular DMA buffer. The output writes records at, or more
+    /// severe than, the DMA logger's [blocking level](../dma/fn.set_blocking_level.html) with
+    /// blocking writes.
+    pub fn dma<T: IntoSink>(
+        tx: T,
+        channel: Channel,
+        buffer: Circular<u8>,
+        config: LoggingConfig,
+    ) -> Self {
+        Output {
+            kind: Kind::Dma(dma::Inner::new(tx, channel, buffer)),
+            max_level: config.max_level,
+            filters: Filters(config.filters),
+        }
+    }
+}
+
+/// An output's writes
+///
+/// The fan-out functions below are generic over this trait, so that the tests can
+/// check them without UARTs.
+trait Sink {
+    fn enabled(&self, metadata: &::log::Metadata) -> bool;
+    fn write(&mut self, args: fmt::Arguments);
+    /// Write `args`, with blocking writes if this is a DMA output
+    fn write_blocking(&mut self, args: fmt::Arguments);
+    fn write_bytes(&mut self, bytes: &[u8]);
+    fn poll(&mut self) -> Poll;
+    fn flush(&mut self);
+}
+
+impl Sink for Output {
+    fn enabled(&self, metadata: &::log::Metadata) -> bool {
+        metadata.level() <= self.max_level && self.filters.is_enabled(metadata)
+    }
+
+    fn write(&mut self, args: fmt::Arguments) {
+        match &mut self.kind {
+            Kind::Blocking(uart) => {
+                // Nothing to do if we time out
+                let _ = uart.write_fmt(args);
+            }
+            Kind::Dma(inner) => inner.write(args),
+        }
+    }
+
+    fn write_blocking(&mut self, args: fmt::Arguments) {
+        match &mut self.kind {
+            Kind::Dma(inner) => inner.write_blocking(args),
+            Kind::Blocking(_) => self.write(args),
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        match &mut self.kind {
+            Kind::Blocking(uart) => {
+                // Nothing to do if we time out
+                let _ = uart.write_bytes(bytes);
+            }
+            Kind::Dma(inner) => {
+                inner.write_bytes(bytes);
+            }
+        }
+    }
+
+    fn poll(&mut self) -> Poll {
+        match &mut self.kind {
+            Kind::Dma(inner) => inner.poll(),
+            Kind::Blocking(_) => Poll::Idle,
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Kind::Blocking(uart) = &mut self.kind {
+            // Nothing to do if we time out
+            let _ = uart.flush();
+        }
+    }
+}
+
+fn enabled_any<S: Sink>(sinks: &[S], metadata: &::log::Metadata) -> bool {
+    sinks.iter().any(|sink| sink.enabled(metadata))
+}
+
+fn log_all<S: Sink>(sinks: &mut [S], record: &::log::Record) {
+    for sink in sinks.iter_mut() {
+        if sink.enabled(record.metadata()) {
+            let write: fn(&mut S, fmt::Arguments) = if dma::is_blocking(record.level()) {
+                S::write_blocking
+            } else {
+                S::write
+            };
+            write(
+                sink,
+                format_args!(
+                    "[{} {}]: {}\r\n",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ),
+            );
+        }
+    }
+}
+
+fn print_all<S: Sink>(sinks: &mut [S], args: fmt::Arguments) {
+    for sink in sinks.iter_mut() {
+        sink.write(args);
+    }
+}
+
+fn write_bytes_all<S: Sink>(sinks: &mut [S], bytes: &[u8]) {
+    for sink in sinks.iter_mut() {
+        sink.write_bytes(bytes);
+    }
+}
+
+fn poll_all<S: Sink>(sinks: &mut [S]) -> Poll {
+    let mut poll = Poll::Idle;
+    for sink in sinks.iter_mut() {
+        if sink.poll() == Poll::Active {
+            poll = Poll::Active;
+        }
+    }
+    poll
+}
+
+fn flush_all<S: Sink>(sinks: &mut [S]) {
+    for sink in sinks.iter_mut() {
+        sink.flush();
+    }
+}
+
+/// Wait for every sink to finish writing
+fn drain_all<S: Sink>(sinks: &mut [S]) {
+    for sink in sinks.iter_mut() {
+        // Nothing to do if we time out
+        let _ = timeout::poll(|| Poll::Idle == sink.poll());
+        sink.flush();
+    }
+}
+
+/// The outputs, if initialized
+static OUTPUTS: TryLock<Option<&'static mut [Output]>> = TryLock::new(None);
+
+/// Run `f` with the outputs in a critical section
+///
+/// Returns `None` if another context holds the outputs.
+fn with_outputs<R>(f: impl FnOnce(&mut Option<&'static mut [Output]>) -> R) -> Option<R> {
+    critical_section::with(|_| OUTPUTS.try_lock().map(|mut outputs| f(&mut outputs)))
+}
+
+pub(crate) fn enabled(metadata: &::log::Metadata) -> bool {
+    with_outputs(|outputs| match outputs {
+        Some(outputs) => enabled_any(outputs, metadata),
+        None => false,
+    })
+    .unwrap_or(false)
+}
+
+pub(crate) fn log(record: &::log::Record) {
+    with_outputs(|outputs| {
+        if let Some(outputs) = outputs {
+            log_all(outputs, record);
+        }
+    });
+}
+
+pub(crate) fn print(args: fmt::Arguments) {
+    with_outputs(|outputs| {
+        if let Some(outputs) = outputs {
+            print_all(outputs, args);
+        }
+    });
+}
+
+pub(crate) fn flush() {
+    with_outputs(|outputs| {
+        if let Some(outputs) = outputs {
+            flush_all(outputs);
+        }
+    });
+}
+
+/// Initialize the tee logger with a collection of outputs
+///
+/// Returns an error if you've already called `init()`, and you haven't
+/// [`release()`](fn.release.html)d the logger. Also returns an error if you've already
+/// specified a logger through another interface.
+///
+/// The `log` crate's max level is the greatest max level of all outputs.
+///
+/// If another logger in this crate is initialized, this logger replaces it. See
+/// [Switching loggers](../index.html#switching-loggers) for more information.
+pub fn init(outputs: &'static mut [Output]) -> Result<(), SetLoggerError> {
+    critical_section::with(move |_| {
+        let mut current = OUTPUTS.try_lock().ok_or(SetLoggerError(()))?;
+        if current.is_some() {
+            return Err(SetLoggerError(()));
+        }
+
+        let max_level = outputs
+            .iter()
+            .map(|output| output.max_level)
+            .max()
+            .unwrap_or(::log::LevelFilter::Off);
+        dispatch::activate(Backend::Tee, max_level)?;
+        early::replay(|bytes| write_bytes_all(outputs, bytes));
+        *current = Some(outputs);
+        Ok(())
+    })
+}
+
+/// Drives the tee logger's DMA outputs
+///
+/// `poll()` polls every DMA output, like [`dma::poll()`](../dma/fn.poll.html). Returns
+/// `Poll::Active` if any output has an active transfer. Returns `Poll::Idle` if there is no
+/// tee logger, or if `poll()` preempted a context that's logging.
+pub fn poll() -> Poll {
+    with_outputs(|outputs| match outputs {
+        Some(outputs) => poll_all(outputs),
+        None => Poll::Idle,
+    })
+    .unwrap_or(Poll::Idle)
+}
+
+/// Release the tee logger's outputs
+///
+/// `release()` waits for all outputs to finish writing, then returns the outputs that
+/// you supplied to `init()`. Each wait is bounded by the
+/// [poll timeout](../blocking/fn.set_poll_timeout.html). You may supply them to `init()` again. After `release()`,
+/// the logger discards all messages until you call `init()` again.
+///
+/// Returns `None` if there is no logger, or if `release()` preempted a context that's logging.
+pub fn release() -> Option<&'static mut [Output]> {
+    // Wait for enqueued data outside of a critical section...
+    let _ = timeout::poll(|| Poll::Idle == poll());
+    // ...then wait for anything that was logged in the meantime.
+    let outputs = with_outputs(|outputs| outputs.take()).flatten()?;
+    drain_all(outputs);
+    Some(outputs)
+}
+
+/// Write `args` through every output, even if another context is writing
+///
+/// Returns `false` if there is no tee logger.
+#[cfg(any(feature = "panic-handler", feature = "fault"))]
+pub(crate) fn emergency_write(args: fmt::Arguments) -> bool {
+    critical_section::with(|_| {
+        // Safety: caller never returns to the context that holds the lock
+        let mut outputs = unsafe { OUTPUTS.steal() };
+        if let Some(outputs) = outputs.as_mut() {
+            for output in outputs.iter_mut() {
+                match &mut output.kind {
+                    Kind::Blocking(uart) => {
+                        // Nothing to do if we time out
+                        let _ = uart.write_fmt(args);
+                        let _ = uart.flush();
+                    }
+                    Kind::Dma(inner) => {
+                        inner.emergency_write(args);
+                    }
+                }
+            }
+            true
+        } else {
+            false
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::{Poll, Sink};
+    use crate::{Filter, Filters, LoggingConfig};
+    use core::fmt::{self, Write};
+    use std::{boxed::Box, string::String};
+
+    /// Collects the written bytes
+    struct Host {
+        bytes: String,
+        max_level: ::log::LevelFilter,
+        filters: Filters,
+        /// The records written with blocking writes
+        blocking: usize,
+    }
+
+    impl Host {
+        fn new(config: LoggingConfig) -> Self {
+            Host {
+                bytes: String::new(),
+                max_level: config.max_level,
+                filters: Filters(config.filters),
+                blocking: 0,
+            }
+        }
+    }
+
+    impl Sink for Host {
+        fn enabled(&self, metadata: &::log::Metadata) -> bool {
+            metadata.level() <= self.max_level && self.filters.is_enabled(metadata)
+        }
+        fn write(&mut self, args: fmt::Arguments) {
+            self.bytes.write_fmt(args).unwrap();
+        }
+        fn write_blocking(&mut self, args: fmt::Arguments) {
+            self.blocking += 1;
+            self.write(args);
+        }
+        fn write_bytes(&mut self, bytes: &[u8]) {
+            self.bytes.push_str(core::str::from_utf8(bytes).unwrap());
+        }
+        fn poll(&mut self) -> Poll {
+            Poll::Idle
+        }
+        fn flush(&mut self) {}
+    }
+
+    fn log(sinks: &mut [Host], level: ::log::Level, target: &str, message: &str) {
+        super::log_all(
+            sinks,
+            &::log::Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn fan_out() {
+        let _lock = crate::tests::lock_logger();
+        crate::dma::set_blocking_level(::log::LevelFilter::Error);
+        static SPI: &[Filter] = &[("spi", None)];
+        let mut sinks = [
+            Host::new(LoggingConfig {
+                max_level: ::log::LevelFilter::Info,
+                filters: &[],
+            }),
+            Host::new(LoggingConfig {
+                max_level: ::log::LevelFilter::Debug,
+                filters: SPI,
+            }),
+        ];
+        let debug = ::log::Metadata::builder()
+            .level(::log::Level::Debug)
+            .target("spi")
+            .build();
+        assert!(super::enabled_any(&sinks, &debug));
+        let trace = ::log::Metadata::builder()
+            .level(::log::Level::Trace)
+            .target("spi")
+            .build();
+        assert!(!super::enabled_any(&sinks, &trace));
+
+        super::write_bytes_all(&mut sinks, b"early\r\n");
+        log(&mut sinks, ::log::Level::Info, "spi", "to both");
+        log(&mut sinks, ::log::Level::Debug, "spi", "to the second");
+        log(&mut sinks, ::log::Level::Info, "i2c", "to the first");
+        log(&mut sinks, ::log::Level::Trace, "spi", "to neither");
+        log(&mut sinks, ::log::Level::Error, "i2c", "blocking");
+        super::print_all(&mut sinks, format_args!("printed\r\n"));
+        assert_eq!(super::poll_all(&mut sinks), Poll::Idle);
+
+        assert_eq!(
+            sinks[0].bytes,
+            "early\r\n[INFO spi]: to both\r\n[INFO i2c]: to the first\r\n\
+             [ERROR i2c]: blocking\r\nprinted\r\n"
+        );
+        assert_eq!(
+            sinks[1].bytes,
+            "early\r\n[INFO spi]: to both\r\n[DEBUG spi]: to the second\r\nprinted\r\n"
+        );
+        assert_eq!(sinks[0].blocking, 1);
+        assert_eq!(sinks[1].blocking, 0);
+        crate::dma::set_blocking_level(::log::LevelFilter::Off);
+    }
+
+    #[test]
+    fn init_and_release() {
+        let _lock = crate::tests::lock_logger();
+        let outputs: &'static mut [super::Output] = Box::leak(Box::new([]));
+        super::init(outputs).unwrap();
+        assert!(super::init(Box::leak(Box::new([]))).is_err());
+        ::log::info!("discarded by every output");
+
+        let outputs = super::release().unwrap();
+        assert!(outputs.is_empty());
+        assert!(super::release().is_none());
+        super::init(outputs).unwrap();
+        assert!(super::release().is_some());
+    }
+}
@@ -0,0 +1,70 @@
+//! Bounds the time that blocking writes wait for a UART
+
+use core::{
+    num::NonZeroU32,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The number of times we poll a full transmit FIFO before
+/// dropping a message. Zero means there is no timeout.
+static TIMEOUT: AtomicU32 = AtomicU32::new(0);
+
+fn timeout() -> Option<NonZeroU32> {
+    NonZeroU32::new(TIMEOUT.load(Ordering::Relaxed))
+}
+
+/// Poll `f` until it returns `true`
+///
+/// Returns `false` if `f` never returned `true` within the
+/// blocking logger's timeout.
+pub(crate) fn poll<F: FnMut() -> bool>(mut f: F) -> bool {
+    match timeout() {
+        Some(polls) => (0..polls.get()).any(|_| f()),
+        None => {
+            while !f() {}
+            true
+        }
+    }
+}
+
+/// Set the blocking logger's timeout, in polls
+///
+/// `polls` is the number of times the logger checks a full transmit FIFO before it
+/// gives up on a message. The count restarts every time the FIFO accepts a byte.
+/// `None` (the default) disables the timeout, and the logger waits forever.
+///
+/// After a timeout, the logger tries to end the partial line with `"\r\n"`. If that also
+/// times out, the next message continues the partial line.
+///
+/// The timeout applies to every blocking write in this crate, including the `lpuart`
+/// loggers' blocking writes. It also bounds the DMA loggers' waits for their transfers,
+/// like before a blocking write, or in `release()`.
+pub fn set_poll_timeout(polls: Option<NonZeroU32>) {
+    TIMEOUT.store(polls.map_or(0, NonZeroU32::get), Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{poll, set_poll_timeout};
+    use core::num::NonZeroU32;
+
+    #[test]
+    fn poll_timeout() {
+        let _lock = crate::tests::lock_logger();
+        set_poll_timeout(NonZeroU32::new(3));
+        let mut polls = 0;
+        assert!(!poll(|| {
+            polls += 1;
+            false
+        }));
+        assert_eq!(polls, 3);
+        assert!(poll(|| true));
+
+        set_poll_timeout(None);
+        let mut polls = 0;
+        assert!(poll(|| {
+            polls += 1;
+            polls == 10
+        }));
+    }
+}